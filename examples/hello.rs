@@ -1,14 +1,12 @@
 //! A simple example that evaluates an expression and prints the result.
 
 use rhai::{Engine, EvalAltResult, Scope};
-use rhai::ast::atom::Atom;
-use rhai::ast::expression::Expression;
 
 
 fn main() -> Result<(), Box<EvalAltResult>> {
     let mut engine = Engine::new();
     let mut scope=Scope::new();
-    scope.set_variable("z".to_string(),  Expression::Atom(Atom::Double(5.0)));
+    scope.push("z", 5.0);
     let c=engine.run_file("./examples/1.md".into());
      //let scope_clone = scope.clone();
 
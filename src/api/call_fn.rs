@@ -0,0 +1,229 @@
+use std::any::Any;
+use std::collections::HashSet;
+use crate::ast::stmt::Statement;
+use crate::types::from_dynamic::cast_output;
+use crate::types::{Dynamic, FromDynamic, Position};
+use crate::{Engine, RhaiResultOf, Scope, AST, ERR};
+
+/// Options for [`Engine::call_fn_with_options`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct CallFnOptions<'t> {
+    /// Value bound to the variable `this` during the call, updated with its final value.
+    pub this_ptr: Option<&'t mut Dynamic>,
+    /// Evaluate the [`AST`] to load its top-level variables before calling the function?
+    /// Default `true`.
+    pub eval_ast: bool,
+    /// Remove the variables added to the [`Scope`] once the call is over? Default `true`.
+    pub rewind_scope: bool,
+}
+
+impl Default for CallFnOptions<'_> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'t> CallFnOptions<'t> {
+    /// Create a default [`CallFnOptions`].
+    #[inline(always)]
+    #[must_use]
+    pub fn new() -> Self {
+        Self { this_ptr: None, eval_ast: true, rewind_scope: true }
+    }
+
+    /// Bind `value` to the variable `this` during the call.
+    #[inline(always)]
+    #[must_use]
+    pub fn bind_this_ptr(mut self, value: &'t mut Dynamic) -> Self {
+        self.this_ptr = Some(value);
+        self
+    }
+
+    /// Set whether to evaluate the [`AST`] before calling the function.
+    #[inline(always)]
+    #[must_use]
+    pub fn eval_ast(mut self, value: bool) -> Self {
+        self.eval_ast = value;
+        self
+    }
+
+    /// Set whether to remove the variables added to the [`Scope`] once the call is over.
+    #[inline(always)]
+    #[must_use]
+    pub fn rewind_scope(mut self, value: bool) -> Self {
+        self.rewind_scope = value;
+        self
+    }
+}
+
+/// Arguments of a script function called from Rust: a tuple of up to six values, each stored as
+/// by [`Dynamic::from`], or a [`Vec`] of [`Dynamic`].
+pub trait FuncArgs {
+    /// Convert into the arguments of the call.
+    fn into_args(self) -> Vec<Dynamic>;
+}
+
+impl FuncArgs for Vec<Dynamic> {
+    #[inline(always)]
+    fn into_args(self) -> Vec<Dynamic> {
+        self
+    }
+}
+
+macro_rules! func_args {
+    ($($arg:ident $param:ident),*) => {
+        impl<$($param: Any + Clone),*> FuncArgs for ($($param,)*) {
+            #[inline]
+            fn into_args(self) -> Vec<Dynamic> {
+                let ($($arg,)*) = self;
+                vec![$(Dynamic::from($arg)),*]
+            }
+        }
+    };
+}
+
+func_args!();
+func_args!(a A);
+func_args!(a A, b B);
+func_args!(a A, b B, c C);
+func_args!(a A, b B, c C, d D);
+func_args!(a A, b B, c C, d D, e E);
+func_args!(a A, b B, c C, d D, e E, f F);
+
+impl Engine {
+    /// Call the function `name` defined at the top level of a compiled script, returning its
+    /// value converted to `T`.
+    ///
+    /// The script is evaluated in `scope` first, and the variables it adds are removed once the
+    /// call is over. See [`call_fn_with_options`][Engine::call_fn_with_options].
+    #[inline]
+    pub fn call_fn<T: FromDynamic>(&self, scope: &mut Scope, ast: &AST, name: impl AsRef<str>, args: impl FuncArgs) -> RhaiResultOf<T> {
+        self.call_fn_with_options(CallFnOptions::new(), scope, ast, name, args)
+    }
+
+    /// Call the function `name` defined at the top level of a compiled script with `options`,
+    /// returning its value converted to `T`.
+    ///
+    /// The function sees the variables of `scope` and can change them with `:=`, which makes
+    /// `scope` a place to keep state from one call to the next.
+    pub fn call_fn_with_options<T: FromDynamic>(
+        &self,
+        options: CallFnOptions,
+        scope: &mut Scope,
+        ast: &AST,
+        name: impl AsRef<str>,
+        args: impl FuncArgs,
+    ) -> RhaiResultOf<T> {
+        let name = name.as_ref();
+        let existing: Option<HashSet<String>> = options.rewind_scope
+            .then(|| scope.iter_raw().map(|(name, ..)| name).collect());
+
+        let result = self.call_script_fn(options.eval_ast, options.this_ptr, scope, ast, name, args.into_args());
+
+        if let Some(existing) = existing {
+            let added: Vec<_> = scope.iter_raw().map(|(name, ..)| name).filter(|name| !existing.contains(name)).collect();
+            for name in added {
+                let _ = scope.remove::<Dynamic>(&name);
+            }
+        }
+        result.and_then(cast_output)
+    }
+
+    /// Call the script function `name`, with the functions of `ast` in scope.
+    fn call_script_fn(
+        &self,
+        eval_ast: bool,
+        this_ptr: Option<&mut Dynamic>,
+        scope: &mut Scope,
+        ast: &AST,
+        name: &str,
+        args: Vec<Dynamic>,
+    ) -> RhaiResultOf<Dynamic> {
+        if eval_ast {
+            let _ = self.eval_ast_with_scope_raw(scope, ast)?;
+        } else {
+            self.prepare_scope(scope)?;
+        }
+
+        let environ = Scope::child(scope);
+        let mut function = None;
+        for statement in &ast.statements {
+            if let Statement::FunctionDefinition(func) = statement {
+                environ.set_function(func.name.clone(), func.clone());
+                if func.name == name {
+                    function = Some(func);
+                }
+            }
+        }
+        let function = function.ok_or_else(|| ERR::ErrorFunctionNotFound(name.to_string(), Position::NONE))?;
+
+        match this_ptr {
+            Some(this) => {
+                environ.set_variable("this".into(), this.clone());
+                let result = function.call(args, &environ);
+                if let Some(value) = environ.get_variable("this") {
+                    *this = value;
+                }
+                result
+            }
+            None => function.call(args, &environ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::INT;
+
+    const SCRIPT: &str = "\
+total = 0
+step = 1
+add(x) =>
+    total := total + x * step
+    total
+bump() =>
+    this := this + 1
+    this
+";
+
+    #[test]
+    fn test_call_fn() {
+        let engine = Engine::new();
+        let ast = engine.compile(SCRIPT).unwrap();
+
+        let mut scope = Scope::new();
+        assert_eq!(engine.call_fn::<INT>(&mut scope, &ast, "add", (5 as INT,)).unwrap(), 5);
+        assert!(scope.is_empty());
+
+        // Keep the state of the script between calls
+        let options = || CallFnOptions::new().rewind_scope(false);
+        engine.call_fn_with_options::<INT>(options(), &mut scope, &ast, "add", (2 as INT,)).unwrap();
+        scope.set_value("step", 10 as INT);
+        let total = engine.call_fn_with_options::<INT>(options().eval_ast(false), &mut scope, &ast, "add", (3 as INT,)).unwrap();
+        assert_eq!(total, 32);
+        assert_eq!(scope.get_value::<INT>("total"), Some(32));
+
+        let mut this = Dynamic::Int(41);
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut this);
+        assert_eq!(engine.call_fn_with_options::<INT>(options, &mut scope, &ast, "bump", ()).unwrap(), 42);
+        assert_eq!(this, Dynamic::Int(42));
+    }
+
+    #[test]
+    fn test_call_fn_errors() {
+        let engine = Engine::new();
+        let ast = engine.compile(SCRIPT).unwrap();
+        let mut scope = Scope::new();
+
+        let err = engine.call_fn::<INT>(&mut scope, &ast, "missing", ()).unwrap_err();
+        assert_eq!(err.to_string(), "Function not found: missing");
+        let err = engine.call_fn::<String>(&mut scope, &ast, "add", (1 as INT,)).unwrap_err();
+        assert_eq!(err.to_string(), "Output type incorrect: int (expecting string)");
+        let err = engine.call_fn::<INT>(&mut scope, &ast, "add", ()).unwrap_err();
+        assert_eq!(err.to_string(), "function `add` is missing an argument for parameter `x`");
+        assert!(scope.is_empty());
+    }
+}
@@ -0,0 +1,21 @@
+use crate::parser::parser::parse_ast;
+use crate::{Engine, RhaiResultOf, Scope, AST, ERR};
+
+impl Engine {
+    /// Compile a script into an [`AST`], which can be run many times without parsing it again.
+    ///
+    /// All syntax errors found in the script are reported at once.
+    #[inline]
+    pub fn compile(&self, script: impl AsRef<str>) -> RhaiResultOf<AST> {
+        parse_ast(script.as_ref()).map_err(ERR::from_parse_errors)
+    }
+
+    /// Compile a script into an [`AST`] to be run with `scope`.
+    ///
+    /// Names are only resolved when the script runs, so the result is the same as that of
+    /// [`compile`][Engine::compile] whatever `scope` holds.
+    #[inline]
+    pub fn compile_with_scope(&self, _scope: &Scope, script: impl AsRef<str>) -> RhaiResultOf<AST> {
+        self.compile(script)
+    }
+}
@@ -0,0 +1,56 @@
+use crate::types::FromDynamic;
+use crate::{Engine, RhaiResultOf, Scope};
+
+impl Engine {
+    /// Evaluate a script, returning the value of its last statement converted to `T`.
+    ///
+    /// See [`eval_ast`][Engine::eval_ast].
+    #[inline]
+    pub fn eval<T: FromDynamic>(&self, script: &str) -> RhaiResultOf<T> {
+        self.eval_with_scope(&mut Scope::new(), script)
+    }
+
+    /// Evaluate a script with `scope`, returning the value of its last statement converted to `T`.
+    ///
+    /// See [`eval_ast_with_scope`][Engine::eval_ast_with_scope].
+    #[inline]
+    pub fn eval_with_scope<T: FromDynamic>(&self, scope: &mut Scope, script: &str) -> RhaiResultOf<T> {
+        let ast = self.compile(script)?;
+        self.eval_ast_with_scope(scope, &ast)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::{Engine, EvalAltResult, Scope, FLOAT, INT};
+
+    #[test]
+    fn test_eval() {
+        let engine = Engine::new();
+        assert_eq!(engine.eval::<INT>("40 + 2").unwrap(), 42);
+        assert_eq!(engine.eval::<FLOAT>("x = 3\nx / 2.0").unwrap(), 1.5);
+        assert!(engine.eval::<bool>("1 < 2").unwrap());
+        assert_eq!(engine.eval::<String>("'a' + 1").unwrap(), "a1");
+        assert_eq!(engine.eval::<Vec<INT>>("[1, 2, 3]").unwrap(), vec![1, 2, 3]);
+        assert_eq!(engine.eval::<(INT, String)>("f(x) => [x * 2, str.tostring(x)]\nf(4)").unwrap(), (8, "4".to_string()));
+        assert_eq!(engine.eval::<Option<INT>>("na").unwrap(), None);
+
+        let mut scope = Scope::new();
+        engine.eval_with_scope::<()>(&mut scope, "x = 1").unwrap();
+        assert_eq!(engine.eval_with_scope::<INT>(&mut scope, "x += 1\nx").unwrap(), 2);
+        assert!(engine.eval::<HashMap<String, INT>>("1").is_err());
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let engine = Engine::new();
+        let err = engine.eval::<INT>("'a'").unwrap_err();
+        assert!(matches!(*err, EvalAltResult::ErrorMismatchOutputType(..)));
+        assert_eq!(err.to_string(), "Output type incorrect: string (expecting int)");
+
+        let err = engine.eval::<INT>("x = y\n1").unwrap_err();
+        assert_eq!(err.to_string(), "Variable not found: y (line 1, position 5)");
+        assert!(Engine::new().run("x = (").is_err());
+    }
+}
@@ -1,5 +1,5 @@
 
-use crate::{Engine, RhaiResultOf, ERR, Scope};
+use crate::{Engine, RhaiResultOf, ERR, Scope, AST};
 #[cfg(feature = "no_std")]
 use std::prelude::v1::*;
 use std::{
@@ -7,7 +7,8 @@ use std::{
     io::Read,
     path::{Path, PathBuf},
 };
-use crate::types::scope;
+use std::collections::HashMap;
+use crate::series::{CsvFeed, CsvOptions, Series};
 
 impl Engine {
     /// Read the contents of a file into a string.
@@ -42,10 +43,33 @@ impl Engine {
 
         Ok(contents)
     }
+    /// Compile a script file into an [`AST`].
+    #[inline]
+    pub fn compile_file(&self, path: PathBuf) -> RhaiResultOf<AST> {
+        Self::read_file(path).and_then(|contents| self.compile(contents))
+    }
+    /// Compile a script file into an [`AST`] to be run with `scope`.
+    #[inline]
+    pub fn compile_file_with_scope(&self, scope: &Scope, path: PathBuf) -> RhaiResultOf<AST> {
+        Self::read_file(path).and_then(|contents| self.compile_with_scope(scope, contents))
+    }
     #[inline]
     pub fn run_file(&mut self, path: PathBuf) -> RhaiResultOf<()> {
         Self::read_file(path).and_then(|contents| self.run(&*contents))
     }
+    /// Run a script file once per bar of a CSV data file.
+    ///
+    /// See [`run_feed`][Engine::run_feed].
+    pub fn run_file_with_data(
+        &self,
+        path: PathBuf,
+        data: PathBuf,
+        options: &CsvOptions,
+    ) -> RhaiResultOf<HashMap<String, Series>> {
+        let contents = Self::read_file(path)?;
+        let feed = CsvFeed::from_path(data, options)?;
+        self.run_feed(&contents, &feed)
+    }
     #[inline]
     pub fn run_file_scope(&mut self, path: PathBuf, scope:&mut Scope) -> RhaiResultOf<()> {
         Self::read_file(path).and_then(|contents| self.run_scope(&*contents,scope))
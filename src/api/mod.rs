@@ -1 +1,4 @@
+pub mod call_fn;
+mod compile;
+mod eval;
 mod files;
\ No newline at end of file
@@ -0,0 +1,11 @@
+use crate::types::Position;
+
+/// Script metadata written as a `//@name=value` or `//@name value` comment,
+/// such as `//@version=5`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub name: String,
+    /// Text after the name, trimmed; empty when the annotation is a bare `//@name`.
+    pub value: String,
+    pub pos: Position,
+}
@@ -0,0 +1,26 @@
+use crate::ast::annotation::Annotation;
+use crate::ast::stmt::Statement;
+
+/// A compiled script.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AST {
+    /// Top-level statements, in order.
+    pub statements: Vec<Statement>,
+    /// Annotation comments at the top level of the script, in order.
+    pub annotations: Vec<Annotation>,
+}
+
+impl AST {
+    /// Value of the first annotation called `name`.
+    pub fn annotation(&self, name: &str) -> Option<&str> {
+        self.annotations
+            .iter()
+            .find(|annotation| annotation.name == name)
+            .map(|annotation| annotation.value.as_str())
+    }
+
+    /// Language version declared by a `//@version=N` annotation.
+    pub fn version(&self) -> Option<u32> {
+        self.annotation("version")?.parse().ok()
+    }
+}
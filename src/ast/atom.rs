@@ -1,4 +1,5 @@
 use crate::ast::expression::Expression;
+use crate::types::{Dynamic, Position};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Atom {
@@ -7,10 +8,26 @@ pub enum Atom {
     Boolean(bool),
     Integer(i64),
     Double(f64),
+    Na,
 }
 
+impl Atom {
+    /// The value of a literal atom, or [`None`] for a variable reference.
+    pub fn to_dynamic(&self) -> Option<Dynamic> {
+        match self {
+            Atom::String(s) => Some(s.clone().into()),
+            Atom::Variable(_) => None,
+            Atom::Boolean(b) => Some((*b).into()),
+            Atom::Integer(n) => Some((*n).into()),
+            Atom::Double(n) => Some((*n).into()),
+            Atom::Na => Some(Dynamic::NA),
+        }
+    }
+}
+
+/// Wrap an [`Atom`] into an [`Expression`] that has no source position.
 impl From<Atom> for Expression {
     fn from(atom: Atom) -> Self {
-        Expression::Atom(atom)
+        Expression::Atom(atom, Position::NONE)
     }
 }
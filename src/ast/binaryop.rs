@@ -1,3 +1,4 @@
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum BinaryOperation {
@@ -5,6 +6,50 @@ pub enum BinaryOperation {
     Minus,
     Times,
     Divide,
+    Modulo,
     Equal,
     NotEqual,
+    LessThan,
+    LessThanEqual,
+    GreaterThan,
+    GreaterThanEqual,
+    And,
+    Or,
+}
+
+impl BinaryOperation {
+    /// Binding power of the operator, higher binds tighter.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOperation::Or => 1,
+            BinaryOperation::And => 2,
+            BinaryOperation::Equal | BinaryOperation::NotEqual => 3,
+            BinaryOperation::LessThan
+            | BinaryOperation::LessThanEqual
+            | BinaryOperation::GreaterThan
+            | BinaryOperation::GreaterThanEqual => 4,
+            BinaryOperation::Plus | BinaryOperation::Minus => 5,
+            BinaryOperation::Times | BinaryOperation::Divide | BinaryOperation::Modulo => 6,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinaryOperation::Plus => "+",
+            BinaryOperation::Minus => "-",
+            BinaryOperation::Times => "*",
+            BinaryOperation::Divide => "/",
+            BinaryOperation::Modulo => "%",
+            BinaryOperation::Equal => "==",
+            BinaryOperation::NotEqual => "!=",
+            BinaryOperation::LessThan => "<",
+            BinaryOperation::LessThanEqual => "<=",
+            BinaryOperation::GreaterThan => ">",
+            BinaryOperation::GreaterThanEqual => ">=",
+            BinaryOperation::And => "and",
+            BinaryOperation::Or => "or",
+        })
+    }
 }
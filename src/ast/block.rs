@@ -1,9 +1,12 @@
 
 use crate::ast::expression::Expression;
 use crate::ast::stmt::Statement;
+use crate::types::Span;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Block {
     pub statements: Vec<Statement>,
-    pub return_expr: Expression,
+    /// Value of the block; [`None`] when it ends with a statement, making the value `na`.
+    pub return_expr: Option<Expression>,
+    pub span: Span,
 }
@@ -1,7 +1,10 @@
 
 use crate::ast::atom::Atom;
 use crate::ast::binaryop::BinaryOperation;
+use crate::ast::block::Block;
 use crate::ast::function::Function;
+use crate::ast::unaryop::UnaryOperation;
+use crate::types::Position;
 
 // #[derive(Debug, Clone, PartialEq)]
 // pub enum Expression2 {
@@ -15,12 +18,47 @@ use crate::ast::function::Function;
 // }
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
-    BinaryOperation(BinaryOperation, Box<Expression>, Box<Expression>),
-    Atom(Atom),
+    BinaryOperation(BinaryOperation, Box<Expression>, Box<Expression>, Position),
+    UnaryOperation(UnaryOperation, Box<Expression>, Position),
+    Atom(Atom, Position),
     Function(Box<Function>),
-    MethodCall(String, String, Vec<Expression>),
-    FunctionCall(String, Vec<Expression>),
-    Tuple(Vec<Expression>),
+    MethodCall(String, String, Vec<Expression>, Position),
+    /// Call of a function with positional arguments, then named `name = value` arguments.
+    FunctionCall(String, Vec<Expression>, Vec<(String, Expression)>, Position),
+    Tuple(Vec<Expression>, Position),
+    /// `expr[offset]`: the value of `expr` `offset` bars ago.
+    History(Box<Expression>, Box<Expression>, Position),
+    /// `if` condition, then-block and optional else-block; `else if` nests another `if` in the else-block.
+    If(Box<Expression>, Box<Block>, Option<Box<Block>>, Position),
+    /// `condition ? then : else`
+    Ternary(Box<Expression>, Box<Expression>, Box<Expression>, Position),
+    /// `for var = from to to [by step]`; the value is that of the last iteration.
+    For(String, Box<Expression>, Box<Expression>, Option<Box<Expression>>, Box<Block>, Position),
+    /// `for var in array`
+    ForIn(String, Box<Expression>, Box<Block>, Position),
+    /// `while condition`
+    While(Box<Expression>, Box<Block>, Position),
+}
+
+impl Expression {
+    /// Get the [position][Position] of this expression in the script.
+    pub fn position(&self) -> Position {
+        match self {
+            Expression::BinaryOperation(.., pos)
+            | Expression::UnaryOperation(.., pos)
+            | Expression::Atom(.., pos)
+            | Expression::MethodCall(.., pos)
+            | Expression::FunctionCall(.., pos)
+            | Expression::Tuple(.., pos)
+            | Expression::History(.., pos)
+            | Expression::If(.., pos)
+            | Expression::Ternary(.., pos)
+            | Expression::For(.., pos)
+            | Expression::ForIn(.., pos)
+            | Expression::While(.., pos) => *pos,
+            Expression::Function(func) => func.span.start(),
+        }
+    }
 }
 
 // #[derive(PartialEq)]
@@ -1,10 +1,12 @@
 use crate::ast::block::Block;
-use crate::ast::expression::Expression;
 use crate::ast::parameter::Parameter;
+use crate::types::Span;
 
 #[derive(Debug, Clone,PartialEq)]
 pub struct Function {
+    /// Name of the function; empty for an anonymous function such as `(x) => x * 2`.
     pub name: String,
     pub parameters: Vec<Parameter>,
     pub body: Block,
+    pub span: Span,
 }
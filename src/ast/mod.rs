@@ -1,8 +1,10 @@
 pub  mod ast;
+pub mod annotation;
 pub mod atom;
 pub mod binaryop;
+pub mod unaryop;
 pub mod expression;
 pub mod function;
 pub mod stmt;
 pub mod block;
-pub mod parameter;
\ No newline at end of file
+pub mod parameter;
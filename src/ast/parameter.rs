@@ -1,7 +1,9 @@
 use crate::ast::expression::Expression;
+use crate::types::Position;
 
 #[derive(Debug, Clone,PartialEq)]
 pub struct Parameter {
     pub name: String,
     pub default_value: Option<Expression>,
+    pub pos: Position,
 }
@@ -1,12 +1,40 @@
 use crate::ast::expression::Expression;
 use crate::ast::function::Function;
+use crate::types::Position;
 
 #[derive(Debug, Clone,PartialEq)]
 pub enum Statement {
-    VariableDeclaration(String, Expression),
-    Assignment(String, Expression),
-    TupleAssignment(Vec<String>, Expression),
+    VariableDeclaration(String, Expression, Position),
+    /// `var name = init` or `varip name = init`: a variable whose initializer runs only on the
+    /// first bar and whose value carries over to the next bars.
+    PersistentDeclaration(String, Expression, Position),
+    Assignment(String, Expression, Position),
+    TupleAssignment(Vec<String>, Expression, Position),
     FunctionDefinition(Function),
-    FunctionCall(String, Vec<Expression>), // Add this variant
+    /// Call of a function with positional arguments, then named `name = value` arguments.
+    FunctionCall(String, Vec<Expression>, Vec<(String, Expression)>, Position),
+    /// An expression evaluated for its effects, such as an `if` statement.
+    Expression(Expression),
+    /// `break` out of the innermost loop.
+    Break(Position),
+    /// `continue` with the next iteration of the innermost loop.
+    Continue(Position),
 
 }
+
+impl Statement {
+    /// Get the [position][Position] of this statement in the script.
+    pub fn position(&self) -> Position {
+        match self {
+            Statement::VariableDeclaration(.., pos)
+            | Statement::PersistentDeclaration(.., pos)
+            | Statement::Assignment(.., pos)
+            | Statement::TupleAssignment(.., pos)
+            | Statement::FunctionCall(.., pos)
+            | Statement::Break(pos)
+            | Statement::Continue(pos) => *pos,
+            Statement::FunctionDefinition(func) => func.span.start(),
+            Statement::Expression(expr) => expr.position(),
+        }
+    }
+}
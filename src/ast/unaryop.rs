@@ -0,0 +1,16 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnaryOperation {
+    Negate,
+    Not,
+}
+
+impl fmt::Display for UnaryOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UnaryOperation::Negate => "-",
+            UnaryOperation::Not => "not",
+        })
+    }
+}
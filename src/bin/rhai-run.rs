@@ -1,4 +1,4 @@
-use rhai::Context;
+use rhai::Engine;
 
 fn main() {
 
@@ -11,8 +11,8 @@ fun(x, y) =>
 [m, n] = fun(8, 5)
 "#;
 
-    let context = Context::new();
-    let result = context.run_ast(code);
+    let engine = Engine::new();
+    let result = engine.run_ast(code);
     println!("Result: {:?}", result);
     match result {
         Ok(vars) => {
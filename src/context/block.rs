@@ -0,0 +1,22 @@
+use crate::ast::block::Block;
+use crate::context::stmt::{persist_variables, run_statements};
+use crate::types::Dynamic;
+use crate::{RhaiResultOf, Scope};
+
+impl Block {
+    /// Run the statements of the block, returning the value of its final expression.
+    ///
+    /// The block runs in a scope of its own: the variables and functions it declares are gone
+    /// once it ends, while `:=` still reaches the variables of the enclosing scopes.
+    pub fn evaluate(&self, context: &mut Scope) -> RhaiResultOf<Dynamic> {
+        let mut local_context = Scope::child(context);
+        let _ = run_statements(&self.statements, &mut local_context)?;
+        let result = match &self.return_expr {
+            Some(expr) => expr.evaluate(&mut local_context),
+            None => Ok(Dynamic::NA),
+        };
+        // The final expression may still change the block's `var` variables
+        persist_variables(&self.statements, &local_context);
+        result
+    }
+}
@@ -1,121 +1,419 @@
 use crate::ast::atom::Atom;
 use crate::ast::binaryop::BinaryOperation;
+use crate::ast::block::Block;
 use crate::ast::expression::Expression;
-use crate::object::object::Object;
-use crate::Scope;
+use crate::ast::unaryop::UnaryOperation;
+use crate::context::function::call_function;
+use crate::types::{Dynamic, FnPtr, Position};
+use crate::{RhaiResultOf, Scope, ERR, INT};
 
 impl Expression {
-    pub fn evaluate(&self, context: &mut Scope) -> Result<Expression, String> {
+    pub fn evaluate(&self, context: &mut Scope) -> RhaiResultOf<Dynamic> {
         match self {
-            Expression::Atom(Atom::Variable(var_name)) => {
+            Expression::Atom(Atom::Variable(var_name), pos) => {
                 if let Some(value) = context.get_variable(var_name) {
-                    value.evaluate(context)
-                } else {
-                    Err(format!("Undefined variable: {}", var_name))
+                    return Ok(value);
+                }
+                // A function name on its own is a pointer to the function
+                match context.resolve_function(var_name) {
+                    Some((function, environ)) => Ok(FnPtr::closure(function, environ).into()),
+                    None if context.natives.borrow().contains_key(var_name) => Ok(FnPtr::new(var_name.clone()).into()),
+                    None => Err(ERR::ErrorVariableNotFound(var_name.clone(), *pos).into()),
                 }
             },
-            Expression::Atom(atom) => Ok(Expression::Atom(atom.clone())),
-            Expression::BinaryOperation(op, left, right) => {
+            Expression::Atom(atom, _) => Ok(atom.to_dynamic().expect("literal atom")),
+            Expression::BinaryOperation(op, left, right, pos) => {
                 let left_val = left.evaluate(context)?;
+                // `and`/`or` short-circuit on the left operand
+                match (op, &left_val) {
+                    (BinaryOperation::And, Dynamic::Bool(false) | Dynamic::Na) => return Ok(Dynamic::FALSE),
+                    (BinaryOperation::Or, Dynamic::Bool(true)) => return Ok(Dynamic::TRUE),
+                    _ => (),
+                }
                 let right_val = right.evaluate(context)?;
-
-                match (left_val, right_val) {
-                    (Expression::Atom(Atom::Integer(l)), Expression::Atom(Atom::Integer(r))) => {
-                        match op {
-                            BinaryOperation::Plus => Ok(Expression::Atom(Atom::Integer(l + r))),
-                            BinaryOperation::Minus => Ok(Expression::Atom(Atom::Integer(l - r))),
-                            BinaryOperation::Times => Ok(Expression::Atom(Atom::Integer(l * r))),
-                            BinaryOperation::Divide => Ok(Expression::Atom(Atom::Integer(l / r))),
-                            _ => Err("Unsupported binary operation".to_string()),
-                        }
-                    }
-                    (Expression::Atom(Atom::Double(l)), Expression::Atom(Atom::Double(r))) => {
-                        match op {
-                            BinaryOperation::Plus => Ok(Expression::Atom(Atom::Double(l + r))),
-                            BinaryOperation::Minus => Ok(Expression::Atom(Atom::Double(l - r))),
-                            BinaryOperation::Times => Ok(Expression::Atom(Atom::Double(l * r))),
-                            BinaryOperation::Divide => Ok(Expression::Atom(Atom::Double(l / r))),
-                            _ => Err("Unsupported binary operation".to_string()),
-                        }
+                evaluate_binary_operation(op, left_val, right_val).map_err(|err| err.fill_position(*pos))
+            }
+            Expression::UnaryOperation(op, operand, pos) => {
+                match (op, operand.evaluate(context)?) {
+                    (_, Dynamic::Na) => Ok(Dynamic::NA),
+                    (UnaryOperation::Negate, Dynamic::Int(v)) => {
+                        checked(v.checked_neg(), "Negation overflow").map_err(|err| err.fill_position(*pos))
                     }
-                    _ => Err("Type error in binary operation".to_string()),
+                    (UnaryOperation::Negate, Dynamic::Float(v)) => Ok((-v).into()),
+                    (UnaryOperation::Not, Dynamic::Bool(v)) => Ok((!v).into()),
+                    (op, value) => Err(ERR::ErrorRuntime(
+                        format!("cannot apply `{}` to {}", op, value.type_name()),
+                        *pos,
+                    ).into()),
                 }
             }
             Expression::Function(func) => {
-                context.set_function(func.name.clone(), *(*func).clone());
-                Ok(Expression::Function(func.clone()))
+                // Anonymous functions are only reachable through the returned pointer
+                if !func.name.is_empty() {
+                    context.set_function(func.name.clone(), (**func).clone());
+                }
+                Ok(FnPtr::closure((**func).clone(), context.share()).into())
             }
-            Expression::Tuple(exprs) => {
-                let evaluated_exprs: Result<Vec<Expression>, String> = exprs.iter()
+            Expression::Tuple(exprs, _) => {
+                let evaluated_exprs: RhaiResultOf<Vec<Dynamic>> = exprs.iter()
                     .map(|e| e.evaluate(context))
                     .collect();
-                Ok(Expression::Tuple(evaluated_exprs?))
+                Ok(Dynamic::Array(evaluated_exprs?))
             }
-            Expression::FunctionCall(name, args) => {
-                let func = context.get_function(name)
-                    .ok_or_else(|| format!("Function '{}' not found", name))?;
-                let evaluated_args: Result<Vec<Expression>, String> = args.iter()
+            Expression::FunctionCall(name, args, named, pos) => {
+                let evaluated_args: RhaiResultOf<Vec<Dynamic>> = args.iter()
                     .map(|arg| arg.evaluate(context))
                     .collect();
-                func.call(evaluated_args?, context)
+                let named = evaluate_named_arguments(named, context)?;
+                call_function(context, name, evaluated_args?, named, *pos)
+            }
+            Expression::History(expr, offset, pos) => {
+                let offset = match offset.evaluate(context)? {
+                    Dynamic::Int(n) if n >= 0 => n as usize,
+                    value => return Err(ERR::ErrorMismatchDataType(
+                        "non-negative int".into(),
+                        value.type_name().into(),
+                        offset.position(),
+                    ).into()),
+                };
+                // Only top-level variables have committed history; parameters and other local
+                // variables are tracked per call site like any other expression
+                if let Expression::Atom(Atom::Variable(name), _) = &**expr {
+                    if offset > 0 && context.is_top_level(name) {
+                        if let Some(value) = context.bars.variable(name, offset) {
+                            return Ok(value);
+                        }
+                    }
+                }
+                let value = expr.evaluate(context)?;
+                Ok(context.bars.site(context.calls.site(*pos), value, offset))
+            }
+            Expression::If(condition, then_block, else_block, _) => {
+                if is_true(condition.evaluate(context)?, condition.position())? {
+                    then_block.evaluate(context)
+                } else if let Some(else_block) = else_block {
+                    else_block.evaluate(context)
+                } else {
+                    Ok(Dynamic::NA)
+                }
+            }
+            Expression::Ternary(condition, then_expr, else_expr, _) => {
+                if is_true(condition.evaluate(context)?, condition.position())? {
+                    then_expr.evaluate(context)
+                } else {
+                    else_expr.evaluate(context)
+                }
+            }
+            Expression::For(var, from, to, step, body, pos) => {
+                let from = from.evaluate(context)?;
+                let to = to.evaluate(context)?;
+                let step = match step {
+                    Some(step) => step.evaluate(context)?,
+                    None => Dynamic::Int(1),
+                };
+                match (from, to, step) {
+                    (Dynamic::Int(from), Dynamic::Int(to), Dynamic::Int(step)) => {
+                        for_range(var, from, to, step, body, context).map_err(|err| err.fill_position(*pos))
+                    }
+                    (from, to, step) => {
+                        let number = |value: Dynamic| value.as_number().map_err(|typ| {
+                            ERR::ErrorMismatchDataType("number".into(), typ.into(), self.position())
+                        });
+                        for_range(var, number(from)?, number(to)?, number(step)?, body, context)
+                            .map_err(|err| err.fill_position(*pos))
+                    }
+                }
+            }
+            Expression::ForIn(var, iterable, body, _) => {
+                let items = match iterable.evaluate(context)? {
+                    Dynamic::Array(items) => items,
+                    value => return Err(ERR::ErrorMismatchDataType(
+                        "array".into(),
+                        value.type_name().into(),
+                        iterable.position(),
+                    ).into()),
+                };
+                // The loop variable lives in a scope of the loop
+                let mut loop_context = Scope::child(context);
+                let mut last = Dynamic::NA;
+                for item in items {
+                    loop_context.set_variable(var.clone(), item);
+                    if !iterate(body, &mut loop_context, &mut last)? {
+                        break;
+                    }
+                }
+                Ok(last)
             }
-            Expression::MethodCall(lib_name, method_name, args) => {
+            Expression::While(condition, body, _) => {
+                let mut last = Dynamic::NA;
+                while is_true(condition.evaluate(context)?, condition.position())? {
+                    if !iterate(body, context, &mut last)? {
+                        break;
+                    }
+                }
+                Ok(last)
+            }
+            // `import name` is a call on the reserved `import`, which loads a library unless it is
+            // already there with its state
+            Expression::MethodCall(lib_name, library, args, pos) if lib_name == "import" && args.is_empty() => {
+                if !context.libraries.borrow().contains_key(library) {
+                    context.import_library(library).map_err(|err| err.fill_position(*pos))?;
+                }
+                Ok(Dynamic::NA)
+            }
+            Expression::MethodCall(lib_name, method_name, args, pos) => {
+                let evaluated_args: RhaiResultOf<Vec<Dynamic>> = args.iter()
+                    .map(|arg| arg.evaluate(context))
+                    .collect();
 
-                println!("lib_name==={:?},{:?},{:?}",lib_name,method_name,args);
+                context
+                    .call_library_function(lib_name, method_name, evaluated_args?, *pos)
+                    .map_err(|err| err.fill_position(*pos))
+            },
+        }
+    }
+}
 
+/// Evaluate the named arguments of a call.
+pub(crate) fn evaluate_named_arguments(named: &[(String, Expression)], context: &mut Scope) -> RhaiResultOf<Vec<(String, Dynamic)>> {
+    named.iter()
+        .map(|(name, expr)| Ok((name.clone(), expr.evaluate(context)?)))
+        .collect()
+}
 
-                let args_clone: Vec<Expression> = args.clone();
+/// Run one iteration of a loop body, keeping its value in `last`.
+///
+/// Returns `false` when the body breaks out of the loop.
+fn iterate(body: &Block, context: &mut Scope, last: &mut Dynamic) -> RhaiResultOf<bool> {
+    match body.evaluate(context) {
+        Ok(value) => {
+            *last = value;
+            Ok(true)
+        }
+        Err(err) => match *err {
+            ERR::LoopBreak(false, ..) => Ok(true),
+            ERR::LoopBreak(true, ..) => Ok(false),
+            _ => Err(err),
+        },
+    }
+}
 
-                println!("111{:?},{:?},{:?}",lib_name,method_name,args);
+/// Run `for var = from to to by step`, counting down when `to` is below `from`.
+fn for_range<T>(var: &str, from: T, to: T, step: T, body: &Block, context: &mut Scope) -> RhaiResultOf<Dynamic>
+where
+    T: Copy + PartialOrd + Default + std::ops::Add<Output = T> + std::ops::Sub<Output = T> + Into<Dynamic>,
+{
+    let zero = T::default();
+    let step = if step < zero { zero - step } else { step };
+    if step == zero {
+        return Err(ERR::ErrorArithmetic("`for` loop step cannot be zero".into(), Position::NONE).into());
+    }
 
-                let result = context.call_library_function(lib_name, method_name, args_clone);
+    // The loop variable lives in a scope of the loop
+    let mut loop_context = Scope::child(context);
+    let ascending = from <= to;
+    let mut counter = from;
+    let mut last = Dynamic::NA;
+    while if ascending { counter <= to } else { counter >= to } {
+        loop_context.set_variable(var.to_string(), counter.into());
+        if !iterate(body, &mut loop_context, &mut last)? {
+            break;
+        }
+        counter = if ascending { counter + step } else { counter - step };
+    }
+    Ok(last)
+}
 
-                println!("result===={:?}",result);
+/// Truth of a condition: `na` is false, anything but a bool is an error.
+pub(crate) fn is_true(value: Dynamic, pos: Position) -> RhaiResultOf<bool> {
+    match value {
+        Dynamic::Bool(b) => Ok(b),
+        Dynamic::Na => Ok(false),
+        value => Err(ERR::ErrorMismatchDataType("bool".into(), value.type_name().into(), pos).into()),
+    }
+}
 
-                match result {
-                    Ok(result) => Ok(result), // Return the result wrapped in Ok
-                    Err(err) => Err(err),     // Return the error
-                }
-                // match result {
-
-
-                //     Ok(expression) => println!("Function call succeeded with result: {:?}", expression),
-                //     Err(err) => eprintln!("Function call failed with error: {:?}", err),
-                // }
-                //     let arg = arg.evaluate(context)?;
-                // if let Some(result) = context.call_library_function(lib_name, method_name, args) {
-                //     Ok(Expression::Atom(Atom::Double(result)))
-                // } else {
-                //     Err(format!("Unsupported method call {}.{}", lib_name, method_name))
-                // }
-                // if lib_name == "import" {
-                //     if let Some(library_name) = args.get(0) {
-                //         if let Expression::Atom(Atom::String(lib_name)) = library_name {
-                //             context.import_library(lib_name);
-                //             Ok(Expression::Atom(Atom::String(format!("Imported library: {}", lib_name))))
-                //         } else {
-                //             Err("Import library name must be a string".to_string())
-                //         }
-                //     } else {
-                //         Err("Import statement must specify a library name".to_string())
-                //     }
-                // } else if let Some(arg) = args.get(0) {
-                //     let arg = arg.evaluate(context)?;
-                //     match arg {
-                //         Expression::Atom(Atom::Double(x)) => {
-                //             if let Some(result) = context.call_library_function(lib_name, method_name, &[x]) {
-                //                 Ok(Expression::Atom(Atom::Double(result)))
-                //             } else {
-                //                 Err(format!("Unsupported method call {}.{}", lib_name, method_name))
-                //             }
-                //         }
-                //         _ => Err("Unsupported argument type for method call".to_string()),
-                //     }
-                // } else {
-                //     Err("Method call must have at least one argument".to_string())
-                // }
+/// Equality of two values, comparing integers and doubles by value. `na` equals nothing.
+fn equals(left: &Dynamic, right: &Dynamic) -> Option<bool> {
+    match (left, right) {
+        (Dynamic::Na, _) | (_, Dynamic::Na) => None,
+        (Dynamic::Int(l), Dynamic::Float(r)) | (Dynamic::Float(r), Dynamic::Int(l)) => Some(*l as f64 == *r),
+        (l, r) => Some(l == r),
+    }
+}
+
+/// Result of checked integer arithmetic, an arithmetic error on overflow.
+fn checked(value: Option<INT>, message: &str) -> RhaiResultOf<Dynamic> {
+    value.map(Dynamic::Int).ok_or_else(|| ERR::ErrorArithmetic(message.into(), Position::NONE).into())
+}
+
+fn evaluate_binary_operation(op: &BinaryOperation, left: Dynamic, right: Dynamic) -> RhaiResultOf<Dynamic> {
+    match op {
+        BinaryOperation::Equal => return Ok(equals(&left, &right).unwrap_or(false).into()),
+        BinaryOperation::NotEqual => return Ok(equals(&left, &right).map_or(false, |eq| !eq).into()),
+        _ => (),
+    }
+
+    match (left, right) {
+        (Dynamic::Int(l), Dynamic::Int(r)) => {
+            match op {
+                BinaryOperation::Divide | BinaryOperation::Modulo if r == 0 => Err(ERR::ErrorArithmetic("Division by zero".to_string(), Position::NONE).into()),
+                BinaryOperation::Plus => checked(l.checked_add(r), "Addition overflow"),
+                BinaryOperation::Minus => checked(l.checked_sub(r), "Subtraction overflow"),
+                BinaryOperation::Times => checked(l.checked_mul(r), "Multiplication overflow"),
+                BinaryOperation::Divide => checked(l.checked_div(r), "Division overflow"),
+                BinaryOperation::Modulo => checked(l.checked_rem(r), "Modulo overflow"),
+                BinaryOperation::LessThan => Ok((l < r).into()),
+                BinaryOperation::LessThanEqual => Ok((l <= r).into()),
+                BinaryOperation::GreaterThan => Ok((l > r).into()),
+                BinaryOperation::GreaterThanEqual => Ok((l >= r).into()),
+                _ => Err("Unsupported binary operation".into()),
+            }
+        }
+        (Dynamic::Float(l), Dynamic::Float(r)) => {
+            match op {
+                BinaryOperation::Plus => Ok((l + r).into()),
+                BinaryOperation::Minus => Ok((l - r).into()),
+                BinaryOperation::Times => Ok((l * r).into()),
+                BinaryOperation::Divide => Ok((l / r).into()),
+                BinaryOperation::Modulo => Ok((l % r).into()),
+                BinaryOperation::LessThan => Ok((l < r).into()),
+                BinaryOperation::LessThanEqual => Ok((l <= r).into()),
+                BinaryOperation::GreaterThan => Ok((l > r).into()),
+                BinaryOperation::GreaterThanEqual => Ok((l >= r).into()),
+                _ => Err("Unsupported binary operation".into()),
+            }
+        }
+        // Mixed integer/double operands are promoted to double
+        (Dynamic::Int(l), right @ Dynamic::Float(_)) => {
+            evaluate_binary_operation(op, Dynamic::Float(l as f64), right)
+        }
+        (left @ Dynamic::Float(_), Dynamic::Int(r)) => {
+            evaluate_binary_operation(op, left, Dynamic::Float(r as f64))
+        }
+        (Dynamic::Bool(l), Dynamic::Bool(r)) => {
+            match op {
+                BinaryOperation::And => Ok((l && r).into()),
+                BinaryOperation::Or => Ok((l || r).into()),
+                _ => Err("Unsupported binary operation".into()),
+            }
+        }
+        // Strings concatenate with strings and numbers
+        (Dynamic::Str(l), Dynamic::Str(r)) if *op == BinaryOperation::Plus => Ok((l + &r).into()),
+        (Dynamic::Str(l), r @ (Dynamic::Int(_) | Dynamic::Float(_))) if *op == BinaryOperation::Plus => {
+            Ok(format!("{}{}", l, r).into())
+        }
+        (l @ (Dynamic::Int(_) | Dynamic::Float(_)), Dynamic::Str(r)) if *op == BinaryOperation::Plus => {
+            Ok(format!("{}{}", l, r).into())
+        }
+        // `na` poisons arithmetic, fails comparisons and is false in logic
+        (l @ Dynamic::Na, r) | (l, r @ Dynamic::Na) => match op {
+            BinaryOperation::Plus
+            | BinaryOperation::Minus
+            | BinaryOperation::Times
+            | BinaryOperation::Divide
+            | BinaryOperation::Modulo => Ok(Dynamic::NA),
+            BinaryOperation::LessThan
+            | BinaryOperation::LessThanEqual
+            | BinaryOperation::GreaterThan
+            | BinaryOperation::GreaterThanEqual
+            | BinaryOperation::And => Ok(Dynamic::FALSE),
+            BinaryOperation::Or => match (l, r) {
+                (Dynamic::Bool(b), _) | (_, Dynamic::Bool(b)) => Ok(b.into()),
+                (Dynamic::Na, Dynamic::Na) => Ok(Dynamic::FALSE),
+                (l, r) => Err(format!("cannot apply `{}` to {} and {}", op, l.type_name(), r.type_name()).into()),
             },
-            _ => Err("Unsupported expression type".to_string()),
+            _ => Err("Unsupported binary operation".into()),
+        },
+        (l, r) => Err(format!("cannot apply `{}` to {} and {}", op, l.type_name(), r.type_name()).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Dynamic;
+    use crate::Engine;
+
+    #[test]
+    fn test_eval_integer_overflow() {
+        let error = |code: &str| Engine::new().run_ast(code).unwrap_err().to_string();
+        assert_eq!(error("x = 9223372036854775807 + 1\n"), "Addition overflow (line 1, position 25)");
+        assert_eq!(error("x = -9223372036854775807 - 2\n"), "Subtraction overflow (line 1, position 26)");
+        assert_eq!(error("x = 4611686018427387904 * 2\n"), "Multiplication overflow (line 1, position 25)");
+        assert_eq!(error("m = -9223372036854775807 - 1\nx = m / -1\n"), "Division overflow (line 2, position 7)");
+        assert_eq!(error("m = -9223372036854775807 - 1\nx = m % -1\n"), "Modulo overflow (line 2, position 7)");
+        assert_eq!(error("m = -9223372036854775807 - 1\nx = -m\n"), "Negation overflow (line 2, position 5)");
+
+        let result = Engine::new().run_ast("x = 9223372036854775806 + 1\ny = -x\n").unwrap();
+        assert_eq!(result["x"], Dynamic::Int(i64::MAX));
+        assert_eq!(result["y"], Dynamic::Int(-i64::MAX));
+    }
+
+    #[test]
+    fn test_eval_if_else() {
+        let code = "x = 5\ny = 0\nif x > 3\n    y := 1\nelse if x > 1\n    y := 2\nelse\n    y := 3\nz = if x < 0\n    1\n";
+        let result = Engine::new().run_ast(code).unwrap();
+        assert_eq!(result["y"], Dynamic::Int(1));
+        assert_eq!(result["z"], Dynamic::NA);
+
+        let code = "x = 0\ny = if x > 3\n    \"big\"\nelse if x > 1\n    \"medium\"\nelse\n    \"small\"\n";
+        assert_eq!(Engine::new().run_ast(code).unwrap()["y"], Dynamic::from("small"));
+
+        let err = Engine::new().run_ast("x = 1\nif x\n    y = 1\n").unwrap_err();
+        assert_eq!(err.to_string(), "Data type incorrect: int (expecting bool) (line 2, position 4)");
+    }
+
+    #[test]
+    fn test_eval_ternary_and_equality() {
+        let code = "a = 2 == 2.0\nb = \"x\" != \"y\"\nc = na == na\nd = a ? 1 : 2\ne = na ? 1 : 2\n";
+        let result = Engine::new().run_ast(code).unwrap();
+        assert_eq!(result["a"], Dynamic::TRUE);
+        assert_eq!(result["b"], Dynamic::TRUE);
+        assert_eq!(result["c"], Dynamic::FALSE);
+        assert_eq!(result["d"], Dynamic::Int(1));
+        assert_eq!(result["e"], Dynamic::Int(2));
+    }
+
+    #[test]
+    fn test_eval_na_in_logic() {
+        // `na` is false on either side of `and`/`or`
+        let code = "a = na and true\nb = true and na\nc = na or true\nd = true or na\n\
+                    e = na or false\nf = false or na\ng = na or na\n";
+        let result = Engine::new().run_ast(code).unwrap();
+        for (name, value) in [("a", false), ("b", false), ("c", true), ("d", true), ("e", false), ("f", false), ("g", false)] {
+            assert_eq!(result[name], Dynamic::Bool(value), "{}", name);
         }
+
+        let err = Engine::new().run_ast("x = na or 1\n").unwrap_err();
+        assert_eq!(err.to_string(), "cannot apply `or` to na and int (line 1, position 8)");
+    }
+
+    #[test]
+    fn test_eval_for_loops() {
+        let code = "s = 0\nfor i = 1 to 10\n    if i == 3\n        continue\n    if i > 5\n        break\n    s := s + i\n";
+        assert_eq!(Engine::new().run_ast(code).unwrap()["s"], Dynamic::Int(12));
+
+        let code = "s = 0\nfor i = 10 to 0 by 3\n    s += i\nlast = for x in [4, 5]\n    x * 2\n";
+        let result = Engine::new().run_ast(code).unwrap();
+        assert_eq!(result["s"], Dynamic::Int(22));
+        assert_eq!(result["last"], Dynamic::Int(10));
+
+        let result = Engine::new().run_ast("y = 0\nfor i = 0.5 to 2\n    y := i\n").unwrap();
+        assert_eq!(result["y"], Dynamic::Float(1.5));
+
+        let err = Engine::new().run_ast("for i = 0 to 1 by 0\n    i\n").unwrap_err();
+        assert_eq!(err.to_string(), "`for` loop step cannot be zero (line 1, position 1)");
+        let err = Engine::new().run_ast("for x in 5\n    x\n").unwrap_err();
+        assert_eq!(err.to_string(), "Data type incorrect: int (expecting array) (line 1, position 10)");
+    }
+
+    #[test]
+    fn test_eval_while_loop() {
+        let code = "n = 0\nwhile true\n    n += 1\n    if n >= 5\n        break\n";
+        assert_eq!(Engine::new().run_ast(code).unwrap()["n"], Dynamic::Int(5));
+
+        let err = Engine::new().run_ast("x = 1\nbreak\n").unwrap_err();
+        assert!(err.is_pseudo_error());
+        assert_eq!(err.to_string(), "'break' must be within a loop (line 2, position 1)");
     }
 }
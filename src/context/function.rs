@@ -1,32 +1,248 @@
-use crate::ast::expression::Expression;
 use crate::ast::function::Function;
-use crate::ast::stmt::Statement;
-use crate::Scope;
+use crate::types::{Dynamic, FnPtr, Position};
+use crate::{RhaiResultOf, Scope, ERR};
 
 impl Function {
-    pub fn call(&self, args: Vec<Expression>, context: &Scope) -> Result<Expression, String> {
-        if args.len() != self.parameters.len() {
-            return Err("Argument count mismatch".to_string());
+    /// Call the function with `args` in a child of `environ`, the scope it was defined in.
+    pub fn call(&self, args: Vec<Dynamic>, environ: &Scope) -> RhaiResultOf<Dynamic> {
+        self.call_with_named(args, Vec::new(), environ)
+    }
+
+    /// Call the function with positional `args` followed by `named` arguments.
+    ///
+    /// Parameters left without an argument take their default value, evaluated in the scope of
+    /// the call so that it can refer to the parameters before it.
+    pub(crate) fn call_with_named(&self, args: Vec<Dynamic>, named: Vec<(String, Dynamic)>, environ: &Scope) -> RhaiResultOf<Dynamic> {
+        let fn_name = if self.name.is_empty() { "<anonymous>" } else { &self.name };
+        if args.len() > self.parameters.len() {
+            return Err(format!(
+                "function `{}` takes {} arguments but {} were given",
+                fn_name,
+                self.parameters.len(),
+                args.len()
+            ).into());
+        }
+
+        let mut values: Vec<Option<Dynamic>> = args.into_iter().map(Some).collect();
+        values.resize(self.parameters.len(), None);
+        for (name, value) in named {
+            let index = self.parameters.iter()
+                .position(|param| param.name == name)
+                .ok_or_else(|| format!("function `{}` has no parameter `{}`", fn_name, name))?;
+            if values[index].is_some() {
+                return Err(format!("function `{}` got more than one value for parameter `{}`", fn_name, name).into());
+            }
+            values[index] = Some(value);
         }
 
-        let mut local_context = Scope::new();
-        local_context.variables.borrow_mut().extend(context.variables.borrow().clone());
+        let _call = environ.calls.enter()?;
+        let mut local_context = Scope::child(environ);
 
-        for (param, arg) in self.parameters.iter().zip(args) {
-            let evaluated_arg = arg.evaluate(&mut local_context)?;
-            local_context.set_variable(param.name.clone(), evaluated_arg);
+        for (param, value) in self.parameters.iter().zip(values) {
+            let value = match (value, &param.default_value) {
+                (Some(value), _) => value,
+                (None, Some(default)) => default.evaluate(&mut local_context)?,
+                (None, None) => return Err(format!(
+                    "function `{}` is missing an argument for parameter `{}`",
+                    fn_name,
+                    param.name
+                ).into()),
+            };
+            local_context.set_variable(param.name.clone(), value);
         }
 
-        for statement in &self.body.statements {
-            match statement {
-                Statement::VariableDeclaration(name, expr) => {
-                    let value = expr.evaluate(&mut local_context)?;
-                    local_context.set_variable(name.clone(), value);
-                }
-                _ => {}
+        self.body.evaluate(&mut local_context).map_err(|err| match *err {
+            // Loops do not extend into the functions called from them
+            ERR::LoopBreak(is_break, _, pos) => {
+                let keyword = if is_break { "break" } else { "continue" };
+                ERR::ErrorRuntime(format!("'{}' must be within a loop", keyword), pos).into()
             }
+            _ => err,
+        })
+    }
+}
+
+impl FnPtr {
+    /// Call the function pointed to, resolving a pointer by name in `context`.
+    pub fn call(&self, args: Vec<Dynamic>, context: &Scope) -> RhaiResultOf<Dynamic> {
+        self.call_with_named(args, Vec::new(), context)
+    }
+
+    /// Call the function pointed to with positional `args` followed by `named` arguments.
+    pub(crate) fn call_with_named(&self, args: Vec<Dynamic>, named: Vec<(String, Dynamic)>, context: &Scope) -> RhaiResultOf<Dynamic> {
+        match self.closure_def() {
+            Some((function, environ)) => function.call_with_named(args, named, environ),
+            None => call_by_name(context, self.fn_name(), args, named).unwrap_or_else(|| {
+                Err(ERR::ErrorFunctionNotFound(self.fn_name().to_string(), Position::NONE).into())
+            }),
         }
+    }
+}
+
+/// Call the function `name`: a variable holding a function pointer, or else a function defined
+/// in `context` or an enclosing scope, or else a Rust function registered with the engine.
+pub(crate) fn call_function(
+    context: &Scope,
+    name: &str,
+    args: Vec<Dynamic>,
+    named: Vec<(String, Dynamic)>,
+    pos: Position,
+) -> RhaiResultOf<Dynamic> {
+    let _site = context.calls.at(pos);
+    let result = match context.get_variable(name) {
+        Some(Dynamic::FnPtr(fn_ptr)) => fn_ptr.call_with_named(args, named, context),
+        _ => match call_by_name(context, name, args, named) {
+            Some(result) => result,
+            None => return Err(ERR::ErrorFunctionNotFound(name.to_string(), pos).into()),
+        },
+    };
+    result.map_err(|err| err.fill_position(pos))
+}
+
+/// Call the function `name` defined in `context` or an enclosing scope, or else the first
+/// overload of the Rust function `name` that takes `args`.
+///
+/// Returns [`None`] if there is no function `name`.
+fn call_by_name(
+    context: &Scope,
+    name: &str,
+    args: Vec<Dynamic>,
+    named: Vec<(String, Dynamic)>,
+) -> Option<RhaiResultOf<Dynamic>> {
+    if let Some((function, environ)) = context.resolve_function(name) {
+        return Some(function.call_with_named(args, named, &environ));
+    }
+
+    let overloads = context.natives.borrow().get(name)?.clone();
+    if !named.is_empty() {
+        return Some(Err(format!("function `{}` does not take named arguments", name).into()));
+    }
+    let result = overloads.iter().find_map(|function| function.call(&args)).unwrap_or_else(|| {
+        let types: Vec<_> = args.iter().map(Dynamic::type_name).collect();
+        Err(format!("function `{}` cannot take ({})", name, types.join(", ")).into())
+    });
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Dynamic;
+    use crate::Engine;
+
+    #[test]
+    fn test_closure_captures_by_reference() {
+        let code = "k = 3\nf = (x) => x * k\na = f(2)\nk = 10\nb = f(2)\n";
+        let result = Engine::new().run_ast(code).unwrap();
+        assert_eq!(result["a"], Dynamic::Int(6));
+        assert_eq!(result["b"], Dynamic::Int(20));
+    }
+
+    #[test]
+    fn test_first_class_functions() {
+        let code = "make(k) => (x) => x * k\ndouble = make(2)\na = double(5)\n\
+                    apply(f, x) => f(x)\nsq(x) => x * x\nb = apply(sq, 4)\nc = apply((y) => y + 1, 4)\n";
+        let result = Engine::new().run_ast(code).unwrap();
+        assert_eq!(result["a"], Dynamic::Int(10));
+        assert_eq!(result["b"], Dynamic::Int(16));
+        assert_eq!(result["c"], Dynamic::Int(5));
+        assert_eq!(result["double"].to_string(), "Fn(<anonymous>)");
+    }
+
+    #[test]
+    fn test_lexical_scope() {
+        // `f` sees the `x` of the scope it is defined in, not the one of its caller
+        let code = "x = 1\nf() => x\ng() =>\n    x = 5\n    f()\na = g()\n";
+        let result = Engine::new().run_ast(code).unwrap();
+        assert_eq!(result["a"], Dynamic::Int(1));
+        assert_eq!(result["x"], Dynamic::Int(1));
+
+        let err = Engine::new().run_ast("y = 5\nb = y(1)\n").unwrap_err();
+        assert_eq!(err.to_string(), "Function not found: y (line 2, position 5)");
+    }
+
+    #[test]
+    fn test_default_and_named_arguments() {
+        let code = "f(src, len = 14, mult = len * 2) => src + len + mult\n\
+                    a = f(1)\nb = f(1, 2)\nc = f(len = 3, src = 10)\nd = f(1, mult = 0)\n";
+        let result = Engine::new().run_ast(code).unwrap();
+        assert_eq!(result["a"], Dynamic::Int(43));
+        assert_eq!(result["b"], Dynamic::Int(7));
+        assert_eq!(result["c"], Dynamic::Int(19));
+        assert_eq!(result["d"], Dynamic::Int(15));
+
+        let result = Engine::new().run_ast("g = (x, k = 2) => x * k\na = g(3)\nb = g(k = 5, x = 1)\n").unwrap();
+        assert_eq!(result["a"], Dynamic::Int(6));
+        assert_eq!(result["b"], Dynamic::Int(5));
+    }
+
+    #[test]
+    fn test_argument_mismatch() {
+        let error = |call: &str| Engine::new().run_ast(&format!("f(a, b) => a + b\nx = {}\n", call)).unwrap_err().to_string();
+        assert_eq!(error("f(1, 2, 3)"), "function `f` takes 2 arguments but 3 were given (line 2, position 5)");
+        assert_eq!(error("f(1)"), "function `f` is missing an argument for parameter `b` (line 2, position 5)");
+        assert_eq!(error("f(1, c = 2)"), "function `f` has no parameter `c` (line 2, position 5)");
+        assert_eq!(error("f(1, a = 2)"), "function `f` got more than one value for parameter `a` (line 2, position 5)");
+    }
+
+    #[test]
+    fn test_recursion() {
+        let code = "fact(n) =>\n    if n <= 1\n        1\n    else\n        n * fact(n - 1)\nr = fact(10)\n\
+                    even(n) => n == 0 ? true : odd(n - 1)\nodd(n) => n == 0 ? false : even(n - 1)\ne = even(10)\n";
+        let result = Engine::new().run_ast(code).unwrap();
+        assert_eq!(result["r"], Dynamic::Int(3628800));
+        assert_eq!(result["e"], Dynamic::Bool(true));
+    }
+
+    #[test]
+    fn test_max_call_levels() {
+        let code = "down(n) => n == 0 ? 0 : down(n - 1)\nr = down(20)\n";
+        let mut engine = Engine::new();
+        assert_eq!(engine.run_ast(code).unwrap()["r"], Dynamic::Int(0));
+
+        engine.set_max_call_levels(10);
+        let err = engine.run_ast(code).unwrap_err();
+        assert!(matches!(*err, crate::ERR::ErrorStackOverflow(..)));
+        assert_eq!(err.to_string(), "Stack overflow (line 1, position 25)");
+
+        let err = Engine::new().run_ast("forever(n) => forever(n + 1)\nr = forever(0)\n").unwrap_err();
+        assert!(matches!(*err, crate::ERR::ErrorStackOverflow(..)));
+    }
+
+    #[test]
+    fn test_native_functions() {
+        let mut engine = Engine::new();
+        let offset = 100;
+        engine
+            .register_fn("add", |a: i64, b: i64| a + b)
+            .register_fn("add", |a: f64, b: f64| a + b + 0.5)
+            .register_fn("add", move |a: i64| a + offset)
+            .register_fn("greet", |name: String| format!("hi {}", name))
+            .register_fn("nothing", || ())
+            .register_fn("checked_div", |a: i64, b: i64| -> crate::RhaiResultOf<i64> {
+                if b == 0 {
+                    Err("division by zero".into())
+                } else {
+                    Ok(a / b)
+                }
+            });
+
+        let code = "a = add(1, 2)\nb = add(1.0, 2)\nc = add(1)\nd = greet('bo')\ne = nothing()\n\
+                    apply(f, x) => f(x, x)\ng = apply(add, 4)\nh = checked_div(7, 2)\n";
+        let result = engine.run_ast(code).unwrap();
+        assert_eq!(result["a"], Dynamic::Int(3));
+        assert_eq!(result["b"], Dynamic::Float(3.5));
+        assert_eq!(result["c"], Dynamic::Int(101));
+        assert_eq!(result["d"], Dynamic::from("hi bo"));
+        assert_eq!(result["e"], Dynamic::NA);
+        assert_eq!(result["g"], Dynamic::Int(8));
+        assert_eq!(result["h"], Dynamic::Int(3));
+
+        // Script functions come first
+        assert_eq!(engine.eval::<i64>("add(a, b) => a * b\nadd(2, 5)").unwrap(), 10);
 
-        self.body.return_expr.evaluate(&mut local_context)
+        let error = |code: &str| engine.run_ast(code).unwrap_err().to_string();
+        assert_eq!(error("x = checked_div(1, 0)\n"), "division by zero (line 1, position 5)");
+        assert_eq!(error("x = add('a', 1)\n"), "function `add` cannot take (string, int) (line 1, position 5)");
+        assert_eq!(error("x = add(1, b = 2)\n"), "function `add` does not take named arguments (line 1, position 5)");
     }
-}
\ No newline at end of file
+}
@@ -1,3 +1,6 @@
+mod block;
 mod expression;
 mod function;
-mod runast;
\ No newline at end of file
+mod runast;
+mod runseries;
+mod stmt;
@@ -1,101 +1,115 @@
 use std::collections::HashMap;
-use nom::character::complete::multispace0;
-use nom::multi::many0;
-use nom::sequence::preceded;
-use crate::ast::expression::Expression;
-use crate::{Engine, Scope};
-use crate::ast::atom::Atom;
-use crate::ast::stmt::Statement;
-use crate::object::object::Object;
-use crate::package::math::Math;
-use crate::parser::parser::parse_statement;
+use crate::{Engine, RhaiResultOf, Scope, AST};
+use crate::context::stmt::run_statements;
+use crate::types::from_dynamic::cast_output;
+use crate::types::{Dynamic, FromDynamic};
 
 impl Engine{
-    pub fn run_ast(&self, code: &str) -> Result<HashMap<String, Expression>, String> {
-        let (remaining, statements) = many0(preceded(multispace0, parse_statement))(code)
-            .map_err(|e| format!("Parse error: {:?}", e))?;
+    /// Run a script once, returning the variables it leaves at the top level.
+    pub fn run_ast(&self, code: &str) -> RhaiResultOf<HashMap<String, Dynamic>> {
+        let ast = self.compile(code)?;
 
-        println!("Parsed statements: {:?}", statements);
-
-        if statements.is_empty() {
-            return Err("No statements parsed".to_string());
+        if ast.statements.is_empty() {
+            return Err("No statements parsed".into());
         }
 
         let mut local_context = Scope::new();
-        local_context.register_library("math", Box::new(Math));
+        self.run_ast_with_scope(&mut local_context, &ast)?;
 
-        // First pass: collect function definitions
-        for statement in &statements {
-            if let Statement::FunctionDefinition(func) = statement {
+        // Create a new HashMap to return the variables
+        let variables = local_context.variables.borrow().clone();
+        Ok(variables)
+    }
 
-               // println!("------{:?},{:?}",func.name.clone(), func.clone());
-                // self.set_function(func.name.clone(), func.clone());
+    /// Evaluate a compiled script, returning the value of its last statement converted to `T`.
+    ///
+    /// That is the value of the final expression or function call, or `na` when the script
+    /// ends with any other statement. Fails with
+    /// [`ErrorMismatchOutputType`][crate::EvalAltResult::ErrorMismatchOutputType] if the value
+    /// is not a `T`.
+    #[inline]
+    pub fn eval_ast<T: FromDynamic>(&self, ast: &AST) -> RhaiResultOf<T> {
+        self.eval_ast_with_scope(&mut Scope::new(), ast)
+    }
 
-                local_context.set_function(func.name.clone(), func.clone());
+    /// Evaluate a compiled script with `scope`, returning the value of its last statement
+    /// converted to `T`.
+    ///
+    /// The script sees the variables of `scope`, and the variables it declares at the top level
+    /// are left in it.
+    #[inline]
+    pub fn eval_ast_with_scope<T: FromDynamic>(&self, scope: &mut Scope, ast: &AST) -> RhaiResultOf<T> {
+        self.eval_ast_with_scope_raw(scope, ast).and_then(cast_output)
+    }
+
+    /// Run a compiled script with `scope`, leaving the variables it declares at the top level in it.
+    #[inline]
+    pub fn run_ast_with_scope(&self, scope: &mut Scope, ast: &AST) -> RhaiResultOf<()> {
+        self.eval_ast_with_scope_raw(scope, ast).map(|_| ())
+    }
+
+    /// Evaluate a compiled script with `scope`, returning the value of its last statement.
+    pub(crate) fn eval_ast_with_scope_raw(&self, scope: &mut Scope, ast: &AST) -> RhaiResultOf<Dynamic> {
+        self.prepare_scope(scope)?;
+        run_statements(&ast.statements, scope)
+    }
+
+    /// Make the built-in libraries, the registered functions and the limits of this engine
+    /// available in `scope`.
+    ///
+    /// Libraries already in `scope` are kept, together with any state they hold.
+    pub(crate) fn prepare_scope(&self, scope: &mut Scope) -> RhaiResultOf<()> {
+        for name in ["math", "ta", "str"] {
+            if !scope.libraries.borrow().contains_key(name) {
+                scope.import_library(name)?;
             }
         }
-        //println!("fuck===={:?}", self.get_function("fun"));
-
-        // Second pass: execute statements
-        for statement in statements {
-            match statement {
-                Statement::VariableDeclaration(name, expr) => {
-                    let value = expr.evaluate(&mut local_context)?;
-                    local_context.set_variable(name, value);
-                }
-                Statement::Assignment(name, expr) => {
-                    let value = expr.evaluate(&mut local_context)?;
-                    local_context.set_variable(name, value);
-                }
-                Statement::TupleAssignment(vars, expr) => {
-                    let tuple = expr.evaluate(&mut local_context)?;
-                    if let Expression::Tuple(elements) = tuple {
-                        if vars.len() == elements.len() {
-                            for (var, element) in vars.into_iter().zip(elements) {
-                                local_context.set_variable(var, element);
-                            }
-                        } else {
-                            return Err("Tuple assignment mismatch".to_string());
-                        }
-                    } else {
-                        return Err("Expected tuple expression".to_string());
-                    }
-                }
-                Statement::FunctionDefinition(_) => {
-                    // Skip function definitions in this pass
-                }
-
-
-                Statement::FunctionCall(name, args) => {
-                    let func = local_context.get_function(&name)
-                        .ok_or_else(|| format!("Function '{}' not found", name))?;
-                    let evaluated_args: Result<Vec<Expression>, String> = args.iter()
-                        .map(|arg| arg.evaluate(&mut local_context))
-                        .collect();
-                    let result = func.call(evaluated_args?, &local_context)?;
-
-                    if let Expression::Tuple(results) = result {
-                        let vars: Vec<String> = args.iter().filter_map(|arg| {
-                            if let Expression::Atom(Atom::Variable(var)) = arg {
-                                Some(var.clone())
-                            } else {
-                                None
-                            }
-                        }).collect();
-
-                        if vars.len() == results.len() {
-                            for (var, res) in vars.into_iter().zip(results) {
-                                local_context.set_variable(var, res);
-                            }
-                        }
-                    }
-                }
-            }
+        *scope.natives.borrow_mut() = self.functions.clone();
+        scope.calls.set_max(self.max_call_levels());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Dynamic;
+    use crate::{Engine, Scope};
+
+    #[test]
+    fn test_compile_once_run_many() {
+        let engine = Engine::new();
+        let ast = engine.compile("double(x) => x * 2\ny = double(n)\ny + 1\n").unwrap();
+
+        for n in 1..=3 {
+            let mut scope = Scope::new();
+            scope.set_variable("n".into(), Dynamic::Int(n));
+            assert_eq!(engine.eval_ast_with_scope::<Dynamic>(&mut scope, &ast).unwrap(), Dynamic::Int(n * 2 + 1));
+            assert_eq!(scope.get_variable("y"), Some(Dynamic::Int(n * 2)));
         }
 
-        // Create a new HashMap to return the variables
-        let variables = local_context.variables.borrow().clone();
-        Ok(variables)
+        let ast = engine.compile("add(a, b) => a + b\nadd(40, 2)\n").unwrap();
+        assert_eq!(engine.eval_ast::<i64>(&ast).unwrap(), 42);
+        engine.eval_ast::<()>(&engine.compile("x = 1\n").unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_run_ast_with_scope() {
+        let engine = Engine::new();
+        let mut scope = Scope::new();
+        engine.run_ast_with_scope(&mut scope, &engine.compile("x = math.abs(-2)\n").unwrap()).unwrap();
+        engine.run_ast_with_scope(&mut scope, &engine.compile("x += 1\n").unwrap()).unwrap();
+        assert_eq!(scope.get_variable("x"), Some(Dynamic::Int(3)));
+
+        let err = engine.compile("x = (1\ny = \n").unwrap_err();
+        assert_eq!(err.to_string().lines().next(), Some("2 syntax errors:"));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_import() {
+        let result = Engine::new().run_ast("import math\nimport str\nx = math.abs(-2)\n").unwrap();
+        assert_eq!(result["x"], Dynamic::Int(2));
+
+        let err = Engine::new().run_ast("x = 1\nimport net\n").unwrap_err();
+        assert_eq!(err.to_string(), "library `net` is not recognized (line 2, position 1)");
+    }
+}
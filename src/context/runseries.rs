@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use crate::context::stmt::run_statements;
+use crate::series::{Bar, DataFeed, Series};
+use crate::{Engine, RhaiResultOf, Scope, INT};
+
+impl Engine {
+    /// Run a script once per bar over `bars`, oldest first.
+    ///
+    /// See [`run_feed`][Engine::run_feed].
+    #[inline]
+    pub fn run_series(&self, code: &str, bars: &[Bar]) -> RhaiResultOf<HashMap<String, Series>> {
+        self.run_feed(code, bars)
+    }
+
+    /// Run a script once per bar of a [`DataFeed`].
+    ///
+    /// On every bar the built-in series `open`, `high`, `low`, `close`, `volume`, `time`
+    /// (milliseconds since the Unix epoch) and `bar_index` hold that bar's data, and `x[n]`
+    /// refers to the value `x` had `n` bars ago (`na` before the first bar).
+    ///
+    /// Returns the full history of every variable, one value per bar.
+    pub fn run_feed(&self, code: &str, feed: &(impl DataFeed + ?Sized)) -> RhaiResultOf<HashMap<String, Series>> {
+        let ast = self.compile(code)?;
+
+        let mut context = Scope::new();
+        self.prepare_scope(&mut context)?;
+
+        for bar in (0..feed.len()).filter_map(|index| feed.bar(index)) {
+            context.set_variable("open".into(), bar.open.into());
+            context.set_variable("high".into(), bar.high.into());
+            context.set_variable("low".into(), bar.low.into());
+            context.set_variable("close".into(), bar.close.into());
+            context.set_variable("volume".into(), bar.volume.into());
+            context.set_variable("time".into(), bar.time.into());
+            context.set_variable("bar_index".into(), (context.bar_index() as INT).into());
+
+            let _ = run_statements(&ast.statements, &mut context)?;
+            context.commit_bar();
+        }
+
+        let history = context.bars.history.borrow().clone();
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::series::bar::fixtures::{bars, closes};
+    use crate::types::Dynamic;
+
+    #[test]
+    fn test_run_series_history() {
+        let engine = Engine::new();
+        let code = "diff = close - close[1]\nprev = diff[1]\n";
+        let result = engine.run_series(code, &closes(&[1.0, 3.0, 6.0])).unwrap();
+
+        let diff: Vec<_> = result["diff"].iter().cloned().collect();
+        assert_eq!(diff, vec![Dynamic::NA, Dynamic::Float(2.0), Dynamic::Float(3.0)]);
+        let prev: Vec<_> = result["prev"].iter().cloned().collect();
+        assert_eq!(prev, vec![Dynamic::NA, Dynamic::NA, Dynamic::Float(2.0)]);
+    }
+
+    #[test]
+    fn test_run_feed_builtins() {
+        let engine = Engine::new();
+        let feed = vec![Bar::new(1000, 1.0, 4.0, 0.5, 2.0, 10.0), Bar::new(2000, 2.0, 5.0, 1.5, 3.0, 20.0)];
+        let code = "range = high - low\nn = bar_index\nt = time\nv = volume[1]\n";
+        let result = engine.run_feed(code, &feed).unwrap();
+
+        assert_eq!(result["range"][1], Dynamic::Float(3.5));
+        assert_eq!(result["n"][1], Dynamic::Int(1));
+        assert_eq!(result["t"][0], Dynamic::Int(1000));
+        assert_eq!(result["v"][1], Dynamic::Float(10.0));
+    }
+
+    #[test]
+    fn test_run_series_function_history() {
+        let engine = Engine::new();
+        let code = "mom(src, n) => src - src[n]\nm = mom(close * 2, 2)\n";
+        let result = engine.run_series(code, &closes(&[1.0, 2.0, 4.0, 8.0])).unwrap();
+
+        assert_eq!(result["m"][1], Dynamic::NA);
+        assert_eq!(result["m"][2], Dynamic::Float(6.0));
+        assert_eq!(result["m"][3], Dynamic::Float(12.0));
+    }
+
+    #[test]
+    fn test_run_series_history_of_parameter() {
+        let engine = Engine::new();
+        let code = "src = 100\nmom(src) => src - src[1]\nm = mom(close)\n";
+        let result = engine.run_series(code, &closes(&[1.0, 2.0, 4.0])).unwrap();
+
+        let m: Vec<_> = result["m"].iter().cloned().collect();
+        assert_eq!(m, vec![Dynamic::NA, Dynamic::Float(1.0), Dynamic::Float(2.0)]);
+    }
+
+    #[test]
+    fn test_run_series_history_per_call_site() {
+        let engine = Engine::new();
+        let feed = bars([1.0, 2.0, 4.0].iter().map(|&c| (c * 10.0, c, c, c, 1.0)));
+        let code = "mom(src) => src - src[1]\na = mom(close)\nb = mom(open)\n";
+        let result = engine.run_series(code, &feed).unwrap();
+
+        let a: Vec<_> = result["a"].iter().cloned().collect();
+        assert_eq!(a, vec![Dynamic::NA, Dynamic::Float(1.0), Dynamic::Float(2.0)]);
+        let b: Vec<_> = result["b"].iter().cloned().collect();
+        assert_eq!(b, vec![Dynamic::NA, Dynamic::Float(10.0), Dynamic::Float(20.0)]);
+    }
+
+    #[test]
+    fn test_persistent_variables() {
+        let engine = Engine::new();
+        let code = "var count = 0\ncount += 1\nvar first = close\nh = na\nif close > 2\n    var highs = 0\n    highs += 1\n    h := highs\n";
+        let result = engine.run_series(code, &closes(&[1.0, 3.0, 6.0, 2.0, 7.0])).unwrap();
+
+        let count: Vec<_> = result["count"].iter().cloned().collect();
+        assert_eq!(count, vec![Dynamic::Int(1), Dynamic::Int(2), Dynamic::Int(3), Dynamic::Int(4), Dynamic::Int(5)]);
+        let first: Vec<_> = result["first"].iter().cloned().collect();
+        assert_eq!(first, vec![Dynamic::Float(1.0); 5]);
+        let h: Vec<_> = result["h"].iter().cloned().collect();
+        assert_eq!(h, vec![Dynamic::NA, Dynamic::Int(1), Dynamic::Int(2), Dynamic::NA, Dynamic::Int(3)]);
+    }
+
+    #[test]
+    fn test_persistent_variables_per_call_site() {
+        let engine = Engine::new();
+        let code = "counter() =>\n    var c = 0\n    c += 1\n    c\na = counter()\nb = counter()\n";
+        let result = engine.run_series(code, &closes(&[1.0, 2.0, 3.0])).unwrap();
+
+        let expected = vec![Dynamic::Int(1), Dynamic::Int(2), Dynamic::Int(3)];
+        assert_eq!(result["a"].iter().cloned().collect::<Vec<_>>(), expected);
+        assert_eq!(result["b"].iter().cloned().collect::<Vec<_>>(), expected);
+
+        // Changes made by the final `if` of a block are kept too
+        let code = "seen = 0\ntick() =>\n    var h = 0\n    seen := h\n    if true\n        h := h + 1\ntick()\n";
+        let result = engine.run_series(code, &closes(&[1.0, 2.0, 3.0])).unwrap();
+        let seen: Vec<_> = result["seen"].iter().cloned().collect();
+        assert_eq!(seen, vec![Dynamic::Int(0), Dynamic::Int(1), Dynamic::Int(2)]);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::ast::stmt::Statement;
+use crate::context::expression::evaluate_named_arguments;
+use crate::context::function::call_function;
+use crate::types::{Dynamic, Position};
+use crate::{RhaiResultOf, Scope, ERR};
+
+impl Statement {
+    /// Execute the statement in `context`, returning the value of an expression or a call and
+    /// `na` for any other statement.
+    ///
+    /// Function definitions do nothing here, as [`run_statements`] registers them beforehand.
+    pub fn execute(&self, context: &mut Scope) -> RhaiResultOf<Dynamic> {
+        match self {
+            Statement::VariableDeclaration(name, expr, pos) => {
+                let value = expr.evaluate(context)?;
+                declare(context, name, value, *pos)?;
+            }
+            Statement::PersistentDeclaration(name, expr, pos) => {
+                // Every call of a function declaring a `var` keeps a variable of its own
+                let site = context.calls.site(*pos);
+                let value = match context.bars.persistent(&site) {
+                    Some(value) => value,
+                    None => {
+                        let value = expr.evaluate(context)?;
+                        context.bars.persist(site, value.clone());
+                        value
+                    }
+                };
+                declare(context, name, value, *pos)?;
+            }
+            Statement::Assignment(name, expr, pos) => {
+                let value = expr.evaluate(context)?;
+                if context.is_constant(name) {
+                    return Err(ERR::ErrorAssignmentToConstant(name.clone(), *pos).into());
+                }
+                if !context.assign_variable(name, value) {
+                    return Err(ERR::ErrorVariableNotFound(name.clone(), *pos).into());
+                }
+            }
+            Statement::TupleAssignment(vars, expr, pos) => {
+                let tuple = expr.evaluate(context)?;
+                if let Dynamic::Array(elements) = tuple {
+                    if vars.len() == elements.len() {
+                        for (var, element) in vars.iter().zip(elements) {
+                            declare(context, var, element, *pos)?;
+                        }
+                    } else {
+                        return Err(ERR::ErrorRuntime("Tuple assignment mismatch".to_string(), *pos).into());
+                    }
+                } else {
+                    return Err(ERR::ErrorMismatchDataType("array".to_string(), tuple.type_name().to_string(), *pos).into());
+                }
+            }
+            Statement::Expression(expr) => return expr.evaluate(context),
+            Statement::Break(pos) => return Err(ERR::LoopBreak(true, Dynamic::NA, *pos).into()),
+            Statement::Continue(pos) => return Err(ERR::LoopBreak(false, Dynamic::NA, *pos).into()),
+            Statement::FunctionDefinition(_) => (),
+            Statement::FunctionCall(name, args, named, pos) => {
+                let evaluated_args: RhaiResultOf<Vec<Dynamic>> = args.iter()
+                    .map(|arg| arg.evaluate(context))
+                    .collect();
+                let named = evaluate_named_arguments(named, context)?;
+                return call_function(context, name, evaluated_args?, named, *pos);
+            }
+        }
+        Ok(Dynamic::NA)
+    }
+}
+
+/// Declare the variable `name` in `context`, unless it is a constant of that very scope.
+fn declare(context: &Scope, name: &str, value: Dynamic, pos: Position) -> RhaiResultOf<()> {
+    if context.variables.borrow().contains_key(name) && context.is_constant(name) {
+        return Err(ERR::ErrorAssignmentToConstant(name.to_string(), pos).into());
+    }
+    context.set_variable(name.to_string(), value);
+    Ok(())
+}
+
+/// Execute `statements` in order: the top level of a script, a function body or any other block.
+/// Returns the value of the last statement, see [`Statement::execute`].
+///
+/// The functions they define are registered first, so that they can be called from anywhere
+/// in the same block. The `var` variables they declare are saved for the next bar at the end.
+pub(crate) fn run_statements(statements: &[Statement], context: &mut Scope) -> RhaiResultOf<Dynamic> {
+    for statement in statements {
+        if let Statement::FunctionDefinition(func) = statement {
+            context.set_function(func.name.clone(), func.clone());
+        }
+    }
+    let result = statements.iter().try_fold(Dynamic::NA, |_, statement| statement.execute(context));
+
+    // Keep the values `var` variables end up with, even when leaving the block early
+    persist_variables(statements, context);
+    result
+}
+
+/// Keep the current values of the `var` variables that `statements` declare in `context` for
+/// the next bars.
+pub(crate) fn persist_variables(statements: &[Statement], context: &Scope) {
+    for statement in statements {
+        if let Statement::PersistentDeclaration(name, _, pos) = statement {
+            let site = context.calls.site(*pos);
+            if context.bars.persistent(&site).is_some() {
+                if let Some(value) = context.variables.borrow().get(name) {
+                    context.bars.persist(site, value.clone());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Dynamic;
+    use crate::Engine;
+
+    #[test]
+    fn test_statements_in_function_body() {
+        let code = "outer(n) =>\n    total = 0\n    for i = 1 to n\n        total := total + i\n    [a, b] = [total, n]\n\
+                    \x20   helper(x) => x * 10\n    if a > 3\n        sq(v) => v * v\n        a := sq(a)\n    helper(a) + b\n\
+                    r = outer(3)\n";
+        let result = Engine::new().run_ast(code).unwrap();
+        assert_eq!(result["r"], Dynamic::Int(363));
+
+        // Functions are hoisted to the top of their block
+        let result = Engine::new().run_ast("r = g(2)\ng(x) => x + 1\n").unwrap();
+        assert_eq!(result["r"], Dynamic::Int(3));
+    }
+
+    #[test]
+    fn test_call_statement_leaves_arguments_alone() {
+        // Results are only assigned back when destructured explicitly
+        let code = "f(a, b) => [a * 10, b * 10]\nmain() =>\n    x = 1\n    y = 2\n    f(x, y)\n    [x, y]\nr = main()\n\
+                    p = 3\nq = 4\nf(p, q)\n[p, q] = f(p, q)\n";
+        let result = Engine::new().run_ast(code).unwrap();
+        assert_eq!(result["r"], Dynamic::Array(vec![Dynamic::Int(1), Dynamic::Int(2)]));
+        assert_eq!(result["p"], Dynamic::Int(30));
+        assert_eq!(result["q"], Dynamic::Int(40));
+    }
+
+    #[test]
+    fn test_loop_control_stays_in_function() {
+        let code = "f(v) =>\n    cnt = 0\n    while cnt < v\n        cnt += 1\n        if cnt == 2\n            break\n    cnt\nr = f(5)\n";
+        let result = Engine::new().run_ast(code).unwrap();
+        assert_eq!(result["r"], Dynamic::Int(2));
+
+        let err = Engine::new().run_ast("f() =>\n    break\nfor i = 1 to 3\n    x = f()\n").unwrap_err();
+        assert_eq!(err.to_string(), "'break' must be within a loop (line 2, position 5)");
+    }
+
+    #[test]
+    fn test_declaration_and_reassignment() {
+        let code = "x = 1\ny = 10\nif true\n    x = 2\n    y := x * 3\n    z = 5\nn = 2\nn *= 4\nn -= 1\nn /= 2\n";
+        let result = Engine::new().run_ast(code).unwrap();
+        // `=` in the block declares its own `x`, `:=` changes the outer `y`
+        assert_eq!(result["x"], Dynamic::Int(1));
+        assert_eq!(result["y"], Dynamic::Int(6));
+        assert_eq!(result["n"], Dynamic::Int(3));
+        assert!(!result.contains_key("z"));
+
+        let err = Engine::new().run_ast("x = 1\nif true\n    z := 2\n").unwrap_err();
+        assert_eq!(err.to_string(), "Variable not found: z (line 3, position 5)");
+        let err = Engine::new().run_ast("if true\n    z = 1\nw = z\n").unwrap_err();
+        assert_eq!(err.to_string(), "Variable not found: z (line 3, position 5)");
+    }
+}
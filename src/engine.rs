@@ -1,15 +1,22 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::thread::scope;
 
 use nom::character::complete::none_of;
 use crate::ast::expression::Expression;
-use crate::ast::function::Function;
-use crate::Scope;
+use crate::types::native_fn::NativeFunction;
+use crate::types::Dynamic;
+use crate::types::RegisterNativeFunction;
+
+/// Default maximum number of nested function calls.
+pub const MAX_CALL_STACK_DEPTH: usize = 64;
 
 pub struct Engine {
-    scopes: Vec<Scope>,
+    /// Rust functions registered for scripts to call, with their overloads in order.
+    pub(crate) functions: BTreeMap<String, Vec<NativeFunction>>,
+    /// Maximum number of nested function calls.
+    max_call_levels: usize,
 }
 
 impl fmt::Debug for Engine {
@@ -35,15 +42,17 @@ impl Default for Engine {
 impl Engine {
     /// An empty raw [`Engine`].
     pub const RAW: Self = Self {
-        scopes: Vec::new(),
+        functions: BTreeMap::new(),
+        max_call_levels: MAX_CALL_STACK_DEPTH,
     };
 
-    /// Create a new [`Engine`].
+    /// Create a new [`Engine`], with the built-in function `tostring` registered.
     #[inline]
     #[must_use]
     pub fn new() -> Self {
         // Create the new scripting Engine
         let mut engine = Self::new_raw();
+        engine.register_fn("tostring", |value: Dynamic| value.to_string());
 
 
         engine
@@ -55,11 +64,33 @@ impl Engine {
     }
 
 
-    pub fn register_fn(&mut self, name: String, function: Function) {
-        // 注册全局函数
-        if let Some(scope) = self.scopes.first_mut() {
-            scope.set_function(name, function);
-        }
+    /// Set the maximum number of nested function calls, recursive or not, that a script may make.
+    ///
+    /// Going deeper fails with [`ErrorStackOverflow`][crate::EvalAltResult::ErrorStackOverflow].
+    #[inline(always)]
+    pub fn set_max_call_levels(&mut self, levels: usize) -> &mut Self {
+        self.max_call_levels = levels;
+        self
+    }
+
+    /// The maximum number of nested function calls allowed.
+    #[inline(always)]
+    #[must_use]
+    pub const fn max_call_levels(&self) -> usize {
+        self.max_call_levels
+    }
+
+    /// Register a Rust function or closure under `name`, for scripts to call just like a function
+    /// they define.
+    ///
+    /// Arguments are converted with [`FromDynamic`][crate::FromDynamic]. A function returning a
+    /// [`RhaiResultOf`][crate::EvalAltResult] raises its errors in the script.
+    ///
+    /// Registering several functions under the same name overloads it: a call goes to the first
+    /// one registered taking as many parameters as there are arguments, of their types.
+    pub fn register_fn<A, M>(&mut self, name: impl Into<String>, func: impl RegisterNativeFunction<A, M>) -> &mut Self {
+        self.functions.entry(name.into()).or_default().push(func.into_native_function());
+        self
     }
 
 
@@ -8,17 +8,32 @@ mod types;
 mod vm;
 mod object;
 mod package;
+mod series;
 
 
 
 
+pub use api::call_fn::{CallFnOptions, FuncArgs};
+pub use ast::annotation::Annotation;
+pub use ast::ast::AST;
 pub use engine::{Engine};
 
 pub use rhai_codegen::*;
+pub use series::{Bar, Column, CsvFeed, CsvOptions, DataFeed, Field, Series, TimeFormat};
 pub use types::{
-    EvalAltResult,Scope
+    Dynamic, EvalAltResult, FnPtr, FromDynamic, NativeReturn, ParseError, ParseErrorType, Position,
+    RegisterNativeFunction, Scope, Span
 };
 
+/// The system integer type.
+pub type INT = i64;
+/// The system floating-point type.
+pub type FLOAT = f64;
+/// Variable-sized array of [`Dynamic`] values.
+pub type Array = Vec<Dynamic>;
+/// Hash map of [`Dynamic`] values with [`String`] keys.
+pub type Map = std::collections::BTreeMap<String, Dynamic>;
+
 type ERR = EvalAltResult;
 /// General evaluation error for Rhai scripts.
 type RhaiError = Box<ERR>;
@@ -1,58 +1,482 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
-use crate::ast::atom::Atom;
-use crate::ast::expression::Expression;
-use crate::ast::function::Function;
+use crate::package::lib::{check_arity, Library, NativeCallContext};
+use crate::types::{Dynamic, Position};
+use crate::types::scope::CallSite;
+use crate::{RhaiResultOf, ERR, FLOAT};
 
-#[derive(Debug,Clone)]
-pub struct TA {
+/// Values of one input or output of an indicator on every bar, `None` being `na`.
+#[derive(Debug, Clone, Default)]
+struct Line {
+    values: Vec<Option<FLOAT>>,
+}
+
+impl Line {
+    fn set(&mut self, bar: usize, value: Option<FLOAT>) {
+        if self.values.len() <= bar {
+            self.values.resize(bar + 1, None);
+        }
+        self.values[bar] = value;
+    }
+
+    fn get(&self, bar: usize, offset: usize) -> Option<FLOAT> {
+        bar.checked_sub(offset).and_then(|index| self.values.get(index).copied().flatten())
+    }
 
-    pub(crate) methods: RefCell<HashMap<String, fn(&TA, Vec<Expression>) -> Result<Expression, String>>>,
+    /// The last `len` values, newest first, or `None` if any of them is `na`.
+    fn window(&self, bar: usize, len: usize) -> Option<Vec<FLOAT>> {
+        (0..len).map(|offset| self.get(bar, offset)).collect()
+    }
 }
 
-impl TA {
-    pub fn new() -> Self {
-        let mut methods =RefCell::new(HashMap::new());
-        //methods.borrow().insert("change".to_string(), TA::handle_change);
+/// State of one `ta.*` call site: the history of its inputs and intermediate results.
+///
+/// Values are recorded by bar index, so calling the same site again on a bar simply
+/// recomputes that bar from the previous ones.
+#[derive(Debug, Clone, Default)]
+struct CallState {
+    lines: Vec<Line>,
+}
 
-         methods.borrow_mut().insert("change".to_string(), TA::handle_change as fn(&TA, Vec<Expression>) -> Result<Expression, String>);
-        //methods.insert("cci".to_string(), TA::handle_cci as fn(&TA, Vec<Expression>) -> Result<Expression, String>);
-        TA { methods }
+impl CallState {
+    fn lines(&mut self, count: usize) -> &mut [Line] {
+        if self.lines.len() < count {
+            self.lines.resize_with(count, Line::default);
+        }
+        &mut self.lines[..count]
     }
+}
 
-    pub fn handle_change(ta: &TA, args: Vec<Expression>) -> Result<Expression, String> {
-        if args.len() == 2 {
-            if let (Expression::Atom(Atom::String(input)), Expression::Atom(Atom::Integer(value))) = (&args[0], &args[1]) {
-                Ok(Expression::Atom(Atom::Integer(ta.change(input, *value))))
-            } else {
-                Err("Invalid arguments for ta.change".to_string())
-            }
-        } else {
-            Err("Invalid number of arguments for ta.change".to_string())
+fn mean(values: &[FLOAT]) -> FLOAT {
+    values.iter().sum::<FLOAT>() / values.len() as FLOAT
+}
+
+/// Weighted moving average of a window, newest first, weighting the newest value most.
+fn weighted_mean(values: &[FLOAT]) -> FLOAT {
+    let len = values.len() as FLOAT;
+    let sum: FLOAT = values.iter().enumerate().map(|(i, v)| v * (len - i as FLOAT)).sum();
+    sum / (len * (len + 1.0) / 2.0)
+}
+
+/// Population standard deviation.
+fn std_dev(values: &[FLOAT]) -> FLOAT {
+    let m = mean(values);
+    mean(&values.iter().map(|v| (v - m).powi(2)).collect::<Vec<_>>()).sqrt()
+}
+
+fn max(values: &[FLOAT]) -> FLOAT {
+    values.iter().copied().fold(FLOAT::NEG_INFINITY, FLOAT::max)
+}
+
+fn min(values: &[FLOAT]) -> FLOAT {
+    values.iter().copied().fold(FLOAT::INFINITY, FLOAT::min)
+}
+
+/// Exponential smoothing of `src` into `out`, seeded with the simple average of the first
+/// `len` values.
+fn smooth(src: &Line, out: &Line, bar: usize, len: usize, alpha: FLOAT) -> Option<FLOAT> {
+    match out.get(bar, 1) {
+        Some(prev) => src.get(bar, 0).map(|value| alpha * value + (1.0 - alpha) * prev),
+        None => src.window(bar, len).map(|values| mean(&values)),
+    }
+}
+
+/// True range from the current high, low and close line.
+fn true_range(high: Option<FLOAT>, low: Option<FLOAT>, close: &Line, bar: usize, handle_na: bool) -> Option<FLOAT> {
+    let (high, low) = (high?, low?);
+    match close.get(bar, 1) {
+        Some(prev) => Some((high - low).max((high - prev).abs()).max((low - prev).abs())),
+        None if handle_na => Some(high - low),
+        None => None,
+    }
+}
+
+fn output(value: Option<FLOAT>) -> Dynamic {
+    value.map_or(Dynamic::NA, Dynamic::Float)
+}
+
+/// Arguments of a `ta.*` call.
+struct Args<'a> {
+    name: &'a str,
+    args: Vec<Dynamic>,
+}
+
+impl<'a> Args<'a> {
+    fn new(name: &'a str, args: Vec<Dynamic>, min: usize, max: usize) -> RhaiResultOf<Self> {
+        check_arity("ta", name, &args, min, max)?;
+        Ok(Self { name, args })
+    }
+
+    fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    /// A numeric series argument, `na` being `None`.
+    fn number(&self, index: usize) -> RhaiResultOf<Option<FLOAT>> {
+        match &self.args[index] {
+            Dynamic::Na => Ok(None),
+            value => value.as_number().map(Some).map_err(|typ| {
+                ERR::ErrorMismatchDataType("number".into(), typ.into(), Position::NONE).into()
+            }),
         }
     }
 
-    pub fn handle_cci(ta: &TA, args: Vec<Expression>) -> Result<Expression, String> {
-        if args.len() == 2 {
-            if let (Expression::Atom(Atom::String(input)), Expression::Atom(Atom::Integer(value))) = (&args[0], &args[1]) {
-                Ok(Expression::Atom(Atom::Double(ta.cci(input, *value))))
-            } else {
-                Err("Invalid arguments for ta.cci".to_string())
-            }
-        } else {
-            Err("Invalid number of arguments for ta.cci".to_string())
+    /// A positive integer length argument, or `default` if not given.
+    fn length(&self, index: usize, default: Option<usize>) -> RhaiResultOf<usize> {
+        match (self.args.get(index), default) {
+            (None, Some(default)) => Ok(default),
+            (Some(Dynamic::Int(n)), _) if *n > 0 => Ok(*n as usize),
+            (value, _) => Err(format!(
+                "ta.{} expects a positive integer length, not {}",
+                self.name,
+                value.map_or("nothing", Dynamic::type_name),
+            ).into()),
         }
     }
 
-    fn change(&self, input: &str, value: i64) -> i64 {
-        value
+    fn boolean(&self, index: usize, default: bool) -> RhaiResultOf<bool> {
+        match self.args.get(index) {
+            None => Ok(default),
+            Some(value) => value.as_bool().map_err(|typ| {
+                ERR::ErrorMismatchDataType("bool".into(), typ.into(), Position::NONE).into()
+            }),
+        }
     }
+}
+
+/// Current value of a built-in series such as `high` or `volume`.
+fn builtin(context: &NativeCallContext, name: &str) -> Option<FLOAT> {
+    context.scope().get_variable(name).and_then(|value| value.as_number().ok())
+}
+
+/// The `ta` library of technical-analysis indicators.
+///
+/// Every call site keeps its own state across bars, so e.g. two `ta.ema` calls in a script
+/// smooth their own inputs independently.
+#[derive(Debug, Default)]
+pub struct TA {
+    states: RefCell<HashMap<CallSite, CallState>>,
+}
 
-    fn cci(&self, input: &str, value: i64) -> f64 {
-        value as f64
+impl TA {
+    pub fn new() -> Self {
+        Self::default()
     }
 }
 
-enum Object {
-    TA(TA),
+impl Library for TA {
+    fn call_method(&self, context: &NativeCallContext, name: &str, args: Vec<Dynamic>) -> RhaiResultOf<Dynamic> {
+        let bar = context.bar_index();
+        let mut states = self.states.borrow_mut();
+        let state = states.entry(context.call_site()).or_default();
+
+        match name {
+            "sma" | "wma" | "stdev" | "cci" => {
+                let args = Args::new(name, args, 2, 2)?;
+                let len = args.length(1, None)?;
+                let [src] = state.lines(1) else { unreachable!() };
+                src.set(bar, args.number(0)?);
+                let value = src.window(bar, len).and_then(|values| match name {
+                    "sma" => Some(mean(&values)),
+                    "wma" => Some(weighted_mean(&values)),
+                    "stdev" => Some(std_dev(&values)),
+                    _ => {
+                        let m = mean(&values);
+                        let deviation = mean(&values.iter().map(|v| (v - m).abs()).collect::<Vec<_>>());
+                        (deviation != 0.0).then(|| (values[0] - m) / (0.015 * deviation))
+                    }
+                });
+                Ok(output(value))
+            }
+            "ema" | "rma" => {
+                let args = Args::new(name, args, 2, 2)?;
+                let len = args.length(1, None)?;
+                let alpha = if name == "ema" { 2.0 / (len as FLOAT + 1.0) } else { 1.0 / len as FLOAT };
+                let [src, out] = state.lines(2) else { unreachable!() };
+                src.set(bar, args.number(0)?);
+                let value = smooth(src, out, bar, len, alpha);
+                out.set(bar, value);
+                Ok(output(value))
+            }
+            "vwma" => {
+                let args = Args::new(name, args, 2, 2)?;
+                let len = args.length(1, None)?;
+                let volume = builtin(context, "volume");
+                let [weighted, volumes] = state.lines(2) else { unreachable!() };
+                weighted.set(bar, args.number(0)?.zip(volume).map(|(src, vol)| src * vol));
+                volumes.set(bar, volume);
+                let value = weighted.window(bar, len)
+                    .zip(volumes.window(bar, len))
+                    .and_then(|(w, v)| {
+                        let total: FLOAT = v.iter().sum();
+                        (total != 0.0).then(|| w.iter().sum::<FLOAT>() / total)
+                    });
+                Ok(output(value))
+            }
+            "rsi" => {
+                let args = Args::new(name, args, 2, 2)?;
+                let len = args.length(1, None)?;
+                let alpha = 1.0 / len as FLOAT;
+                let [src, up, down, avg_up, avg_down] = state.lines(5) else { unreachable!() };
+                src.set(bar, args.number(0)?);
+                let change = src.get(bar, 0).zip(src.get(bar, 1)).map(|(now, prev)| now - prev);
+                up.set(bar, change.map(|c| c.max(0.0)));
+                down.set(bar, change.map(|c| (-c).max(0.0)));
+                let u = smooth(up, avg_up, bar, len, alpha);
+                let d = smooth(down, avg_down, bar, len, alpha);
+                avg_up.set(bar, u);
+                avg_down.set(bar, d);
+                let value = u.zip(d).map(|(u, d)| {
+                    if d == 0.0 {
+                        100.0
+                    } else if u == 0.0 {
+                        0.0
+                    } else {
+                        100.0 - 100.0 / (1.0 + u / d)
+                    }
+                });
+                Ok(output(value))
+            }
+            "macd" => {
+                let args = Args::new(name, args, 4, 4)?;
+                let (fast_len, slow_len, signal_len) = (args.length(1, None)?, args.length(2, None)?, args.length(3, None)?);
+                let [src, fast, slow, macd, signal] = state.lines(5) else { unreachable!() };
+                src.set(bar, args.number(0)?);
+                let fast_value = smooth(src, fast, bar, fast_len, 2.0 / (fast_len as FLOAT + 1.0));
+                let slow_value = smooth(src, slow, bar, slow_len, 2.0 / (slow_len as FLOAT + 1.0));
+                fast.set(bar, fast_value);
+                slow.set(bar, slow_value);
+                let macd_value = fast_value.zip(slow_value).map(|(f, s)| f - s);
+                macd.set(bar, macd_value);
+                let signal_value = smooth(macd, signal, bar, signal_len, 2.0 / (signal_len as FLOAT + 1.0));
+                signal.set(bar, signal_value);
+                let hist = macd_value.zip(signal_value).map(|(m, s)| m - s);
+                Ok(vec![output(macd_value), output(signal_value), output(hist)].into())
+            }
+            "stoch" => {
+                let args = Args::new(name, args, 4, 4)?;
+                let len = args.length(3, None)?;
+                let [highs, lows] = state.lines(2) else { unreachable!() };
+                highs.set(bar, args.number(1)?);
+                lows.set(bar, args.number(2)?);
+                let value = args.number(0)?
+                    .zip(highs.window(bar, len))
+                    .zip(lows.window(bar, len))
+                    .and_then(|((src, h), l)| {
+                        let (highest, lowest) = (max(&h), min(&l));
+                        (highest != lowest).then(|| 100.0 * (src - lowest) / (highest - lowest))
+                    });
+                Ok(output(value))
+            }
+            "tr" => {
+                let args = Args::new(name, args, 0, 1)?;
+                let handle_na = args.boolean(0, false)?;
+                let [close] = state.lines(1) else { unreachable!() };
+                close.set(bar, builtin(context, "close"));
+                let value = true_range(builtin(context, "high"), builtin(context, "low"), close, bar, handle_na);
+                Ok(output(value))
+            }
+            "atr" => {
+                let args = Args::new(name, args, 1, 1)?;
+                let len = args.length(0, None)?;
+                let [close, range, out] = state.lines(3) else { unreachable!() };
+                close.set(bar, builtin(context, "close"));
+                range.set(bar, true_range(builtin(context, "high"), builtin(context, "low"), close, bar, true));
+                let value = smooth(range, out, bar, len, 1.0 / len as FLOAT);
+                out.set(bar, value);
+                Ok(output(value))
+            }
+            "bb" => {
+                let args = Args::new(name, args, 3, 3)?;
+                let len = args.length(1, None)?;
+                let mult = args.number(2)?;
+                let [src] = state.lines(1) else { unreachable!() };
+                src.set(bar, args.number(0)?);
+                let (basis, upper, lower) = match src.window(bar, len).zip(mult) {
+                    Some((values, mult)) => {
+                        let (basis, dev) = (mean(&values), mult * std_dev(&values));
+                        (Some(basis), Some(basis + dev), Some(basis - dev))
+                    }
+                    None => (None, None, None),
+                };
+                Ok(vec![output(basis), output(upper), output(lower)].into())
+            }
+            "change" => {
+                let args = Args::new(name, args, 1, 2)?;
+                let len = args.length(1, Some(1))?;
+                let [src] = state.lines(1) else { unreachable!() };
+                src.set(bar, args.number(0)?);
+                Ok(output(src.get(bar, 0).zip(src.get(bar, len)).map(|(now, prev)| now - prev)))
+            }
+            "highest" | "lowest" => {
+                let args = Args::new(name, args, 1, 2)?;
+                let (value, len) = match args.len() {
+                    1 => (builtin(context, if name == "highest" { "high" } else { "low" }), args.length(0, None)?),
+                    _ => (args.number(0)?, args.length(1, None)?),
+                };
+                let [src] = state.lines(1) else { unreachable!() };
+                src.set(bar, value);
+                let extreme = if name == "highest" { max } else { min };
+                Ok(output(src.window(bar, len).map(|values| extreme(&values))))
+            }
+            "crossover" | "crossunder" => {
+                let args = Args::new(name, args, 2, 2)?;
+                let [a, b] = state.lines(2) else { unreachable!() };
+                a.set(bar, args.number(0)?);
+                b.set(bar, args.number(1)?);
+                let crossed = match (a.get(bar, 0), b.get(bar, 0), a.get(bar, 1), b.get(bar, 1)) {
+                    (Some(a), Some(b), Some(pa), Some(pb)) if name == "crossover" => a > b && pa <= pb,
+                    (Some(a), Some(b), Some(pa), Some(pb)) => a < b && pa >= pb,
+                    _ => false,
+                };
+                Ok(crossed.into())
+            }
+            "cum" => {
+                let args = Args::new(name, args, 1, 1)?;
+                let [out] = state.lines(1) else { unreachable!() };
+                let value = out.get(bar, 1).unwrap_or(0.0) + args.number(0)?.unwrap_or(0.0);
+                out.set(bar, Some(value));
+                Ok(value.into())
+            }
+            "pivothigh" | "pivotlow" => {
+                let args = Args::new(name, args, 2, 3)?;
+                let (value, left, right) = match args.len() {
+                    2 => (
+                        builtin(context, if name == "pivothigh" { "high" } else { "low" }),
+                        args.length(0, None)?,
+                        args.length(1, None)?,
+                    ),
+                    _ => (args.number(0)?, args.length(1, None)?, args.length(2, None)?),
+                };
+                let [src] = state.lines(1) else { unreachable!() };
+                src.set(bar, value);
+                let pivot = src.window(bar, left + right + 1).and_then(|values| {
+                    let candidate = values[right];
+                    let mut others = values.iter().enumerate().filter(|&(i, _)| i != right).map(|(_, v)| *v);
+                    let is_pivot = if name == "pivothigh" {
+                        others.all(|v| v < candidate)
+                    } else {
+                        others.all(|v| v > candidate)
+                    };
+                    is_pivot.then_some(candidate)
+                });
+                Ok(output(pivot))
+            }
+            _ => Err(ERR::ErrorFunctionNotFound(format!("ta.{}", name), Position::NONE).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::series::Bar;
+    use crate::series::bar::fixtures::{bars, closes};
+    use crate::types::Dynamic;
+    use crate::{Engine, FLOAT};
+
+    const WILDER: [FLOAT; 20] = [
+        44.34, 44.09, 44.15, 43.61, 44.33, 44.83, 45.10, 45.42, 45.84, 46.08,
+        45.89, 46.03, 45.61, 46.28, 46.28, 46.00, 46.03, 46.41, 46.22, 45.64,
+    ];
+
+    const HLC: [(FLOAT, FLOAT, FLOAT); 5] = [
+        (10.0, 8.0, 9.0),
+        (11.0, 9.0, 10.5),
+        (12.0, 10.0, 11.0),
+        (11.5, 9.5, 10.0),
+        (13.0, 10.0, 12.5),
+    ];
+
+    fn hlc() -> Vec<Bar> {
+        bars(HLC.iter().enumerate().map(|(i, &(h, l, c))| (c, h, l, c, (i + 1) as FLOAT)))
+    }
+
+    /// Run `code` and return the history of `var`, rounded to 4 decimals.
+    fn run(code: &str, bars: &[Bar], var: &str) -> Vec<Option<FLOAT>> {
+        let result = Engine::new().run_series(code, bars).unwrap();
+        result[var].iter().map(|value| match value {
+            Dynamic::Na => None,
+            value => Some((value.as_number().unwrap() * 10_000.0).round() / 10_000.0),
+        }).collect()
+    }
+
+    #[test]
+    fn test_ta_moving_averages() {
+        let sma = run("x = ta.sma(close, 5)", &closes(&WILDER), "x");
+        assert_eq!(sma[3], None);
+        assert_eq!(&sma[17..], &[Some(46.2), Some(46.188), Some(46.06)]);
+
+        let ema = run("x = ta.ema(close, 10)", &closes(&WILDER), "x");
+        assert_eq!(ema[8], None);
+        assert_eq!(&ema[9..12], &[Some(44.779), Some(44.981), Some(45.1717)]);
+        assert_eq!(ema[19], Some(45.8704));
+
+        assert_eq!(run("x = ta.wma(close, 5)", &closes(&WILDER), "x")[19], Some(46.0247));
+        assert_eq!(run("x = ta.vwma(close, 2)", &hlc(), "x")[4], Some(11.3889));
+    }
+
+    #[test]
+    fn test_ta_oscillators() {
+        let rsi = run("x = ta.rsi(close, 14)", &closes(&WILDER), "x");
+        assert_eq!(rsi[13], None);
+        assert_eq!(&rsi[14..], &[Some(70.4641), Some(66.2496), Some(66.4809), Some(69.3469), Some(66.2947), Some(57.915)]);
+
+        assert_eq!(run("x = ta.cci(close, 5)", &closes(&WILDER), "x")[19], Some(-137.2549));
+        assert_eq!(run("x = ta.stoch(close, high, low, 3)", &hlc(), "x")[4], Some(85.7143));
+
+        let code = "[m, s, h] = ta.macd(close, 3, 6, 4)\nd = m - (ta.ema(close, 3) - ta.ema(close, 6))\ne = h - (m - s)\n";
+        assert_eq!(run(code, &closes(&WILDER), "d")[19], Some(0.0));
+        assert_eq!(run(code, &closes(&WILDER), "e")[19], Some(0.0));
+        assert_eq!(run(code, &closes(&WILDER), "s")[7], None);
+        assert!(run(code, &closes(&WILDER), "s")[8].is_some());
+    }
+
+    #[test]
+    fn test_ta_volatility() {
+        assert_eq!(run("x = ta.tr()", &hlc(), "x"), vec![None, Some(2.0), Some(2.0), Some(2.0), Some(3.0)]);
+        assert_eq!(run("x = ta.tr(true)", &hlc(), "x")[0], Some(2.0));
+        assert_eq!(run("x = ta.atr(3)", &hlc(), "x"), vec![None, None, Some(2.0), Some(2.0), Some(2.3333)]);
+
+        let code = "[b, u, l] = ta.bb(close, 5, 2)\nsd = ta.stdev(close, 5)\n";
+        assert_eq!(run(code, &closes(&WILDER), "b")[19], Some(46.06));
+        assert_eq!(run(code, &closes(&WILDER), "u")[19], Some(46.573));
+        assert_eq!(run(code, &closes(&WILDER), "l")[19], Some(45.547));
+        assert_eq!(run(code, &closes(&WILDER), "sd")[19], Some(0.2565));
+    }
+
+    #[test]
+    fn test_ta_series_helpers() {
+        assert_eq!(run("x = ta.highest(3)", &hlc(), "x")[4], Some(13.0));
+        assert_eq!(run("x = ta.lowest(3)", &hlc(), "x")[4], Some(9.5));
+        assert_eq!(run("x = ta.highest(close, 2)", &hlc(), "x"), vec![None, Some(10.5), Some(11.0), Some(11.0), Some(12.5)]);
+        assert_eq!(run("x = ta.change(close)", &hlc(), "x")[4], Some(2.5));
+        assert_eq!(run("x = ta.change(close, 2)", &hlc(), "x")[4], Some(1.5));
+        assert_eq!(run("x = ta.cum(close)", &hlc(), "x")[4], Some(53.0));
+        assert_eq!(run("x = ta.pivothigh(close, 1, 1)", &hlc(), "x"), vec![None, None, None, Some(11.0), None]);
+        assert_eq!(run("x = ta.pivotlow(close, 1, 1)", &hlc(), "x")[4], Some(10.0));
+
+        let result = Engine::new().run_series("up = ta.crossover(close, 10.2)\ndown = ta.crossunder(close, 10.2)\n", &hlc()).unwrap();
+        let up: Vec<_> = result["up"].iter().cloned().collect();
+        assert_eq!(up, vec![Dynamic::FALSE, Dynamic::TRUE, Dynamic::FALSE, Dynamic::FALSE, Dynamic::TRUE]);
+        assert_eq!(result["down"][3], Dynamic::TRUE);
+    }
+
+    #[test]
+    fn test_ta_call_sites_are_independent() {
+        let code = "a = ta.cum(1)\nb = ta.cum(close)\nf(x) => ta.cum(x)\nc = f(2)\n";
+        let data = closes(&[1.0, 2.0, 3.0]);
+        assert_eq!(run(code, &data, "a")[2], Some(3.0));
+        assert_eq!(run(code, &data, "b")[2], Some(6.0));
+        assert_eq!(run(code, &data, "c")[2], Some(6.0));
+
+        let code = "f(x) => ta.sma(x, 2)\na = f(close)\nb = f(open)\n";
+        let feed = bars([1.0, 2.0, 3.0, 4.0].iter().map(|&c| (c * 10.0, c, c, c, 1.0)));
+        assert_eq!(run(code, &feed, "a"), vec![None, Some(1.5), Some(2.5), Some(3.5)]);
+        assert_eq!(run(code, &feed, "b"), vec![None, Some(15.0), Some(25.0), Some(35.0)]);
+
+        let err = Engine::new().run_series("x = ta.sma(close, 0)", &data).unwrap_err();
+        assert!(err.to_string().contains("ta.sma expects a positive integer length"));
+        let err = Engine::new().run_series("x = ta.rsi(close)", &data).unwrap_err();
+        assert!(err.to_string().contains("ta.rsi expects 2 arguments but 1 were given"));
+    }
 }
@@ -1,6 +1,73 @@
 use std::fmt;
-use crate::ast::expression::Expression;
+use crate::types::{Dynamic, Position};
+use crate::types::scope::CallSite;
+use crate::{RhaiResultOf, Scope};
+
+/// Context of a call to a [`Library`] function.
+#[derive(Debug, Clone, Copy)]
+pub struct NativeCallContext<'a> {
+    fn_name: &'a str,
+    position: Position,
+    scope: &'a Scope,
+}
+
+impl<'a> NativeCallContext<'a> {
+    /// Create a new [`NativeCallContext`].
+    #[inline]
+    #[must_use]
+    pub fn new(fn_name: &'a str, position: Position, scope: &'a Scope) -> Self {
+        Self { fn_name, position, scope }
+    }
+
+    /// Name of the function called.
+    #[inline]
+    #[must_use]
+    pub const fn fn_name(&self) -> &str {
+        self.fn_name
+    }
+
+    /// [Position] of the call in the script, identifying the call site.
+    #[inline]
+    #[must_use]
+    pub const fn position(&self) -> Position {
+        self.position
+    }
+
+    /// The [`Scope`] the function is called from.
+    #[inline]
+    #[must_use]
+    pub const fn scope(&self) -> &Scope {
+        self.scope
+    }
+
+    /// Index of the bar being executed.
+    #[inline]
+    #[must_use]
+    pub fn bar_index(&self) -> usize {
+        self.scope.bar_index()
+    }
+
+    /// The call site together with the chain of function calls leading to it, telling apart
+    /// calls from the same place in a function called from different places.
+    #[must_use]
+    pub(crate) fn call_site(&self) -> CallSite {
+        self.scope.calls.site(self.position)
+    }
+}
+
+/// Check the number of arguments passed to `lib.name`.
+pub(crate) fn check_arity(lib: &str, name: &str, args: &[Dynamic], min: usize, max: usize) -> RhaiResultOf<()> {
+    if args.len() < min || args.len() > max {
+        let expected = match (min, max) {
+            (min, max) if min == max => min.to_string(),
+            (min, usize::MAX) => format!("at least {}", min),
+            (min, max) => format!("{} to {}", min, max),
+        };
+        return Err(format!("{}.{} expects {} arguments but {} were given", lib, name, expected, args.len()).into());
+    }
+    Ok(())
+}
 
 pub trait Library: fmt::Debug {
-    fn call_method(&self, name: &str, args: Vec<Expression>) -> Result<Expression, String>;
+    fn call_method(&self, context: &NativeCallContext, name: &str, args: Vec<Dynamic>) -> RhaiResultOf<Dynamic>;
 }
\ No newline at end of file
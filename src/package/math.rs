@@ -1,25 +1,285 @@
-use std::fmt::{Debug, Formatter};
-use crate::ast::expression::Expression;
-use crate::ast::atom::Atom;
-use crate::package::lib::Library;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::package::lib::{check_arity, Library, NativeCallContext};
+use crate::types::{Dynamic, Position};
+use crate::types::scope::CallSite;
+use crate::{RhaiResultOf, ERR, FLOAT, INT};
 
+/// A numeric argument: integers stay integers until an operation needs a float.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Number {
+    Int(INT),
+    Float(FLOAT),
+}
+
+impl Number {
+    fn to_float(self) -> FLOAT {
+        match self {
+            Number::Int(n) => n as FLOAT,
+            Number::Float(f) => f,
+        }
+    }
+}
+
+impl From<Number> for Dynamic {
+    fn from(n: Number) -> Self {
+        match n {
+            Number::Int(n) => Dynamic::Int(n),
+            Number::Float(f) => Dynamic::Float(f),
+        }
+    }
+}
+
+/// Convert the arguments to numbers, or `None` if any of them is `na`.
+fn numbers(args: &[Dynamic]) -> RhaiResultOf<Option<Vec<Number>>> {
+    let mut numbers = Vec::with_capacity(args.len());
+    for arg in args {
+        match arg {
+            Dynamic::Int(n) => numbers.push(Number::Int(*n)),
+            Dynamic::Float(f) => numbers.push(Number::Float(*f)),
+            Dynamic::Na => return Ok(None),
+            value => return Err(ERR::ErrorMismatchDataType("number".into(), value.type_name().into(), Position::NONE).into()),
+        }
+    }
+    Ok(Some(numbers))
+}
+
+/// Fold numbers, staying integral while all of them are integers and promoting to float otherwise.
+fn fold(numbers: &[Number], int: fn(INT, INT) -> INT, float: fn(FLOAT, FLOAT) -> FLOAT) -> Number {
+    numbers[1..].iter().fold(numbers[0], |acc, &n| match (acc, n) {
+        (Number::Int(a), Number::Int(b)) => Number::Int(int(a, b)),
+        (a, b) => Number::Float(float(a.to_float(), b.to_float())),
+    })
+}
+
+/// A float result, with the out-of-domain `NaN` turned into `na`.
+fn float(value: FLOAT) -> Dynamic {
+    if value.is_nan() { Dynamic::NA } else { Dynamic::Float(value) }
+}
+
+/// Round a float to an integer, or `na` if it does not fit.
+fn to_int(value: FLOAT) -> Dynamic {
+    if value.is_finite() && value >= INT::MIN as FLOAT && value <= INT::MAX as FLOAT {
+        Dynamic::Int(value as INT)
+    } else {
+        Dynamic::NA
+    }
+}
+
+/// `xorshift64*` pseudo-random number generator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Scramble the seed with `splitmix64` so that close seeds diverge; the state must never be zero
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Rng((z ^ (z >> 31)).max(1))
+    }
+
+    /// Next number in `[0, 1)`.
+    fn next(&mut self) -> FLOAT {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as FLOAT / (1u64 << 53) as FLOAT
+    }
+}
+
+/// The `math` library.
+///
+/// Integer arguments are kept as integers where the result is integral (`abs`, `sign`,
+/// `min`, `max`, `sum`, and `round`, `floor` and `ceil` which always return integers) and
+/// promoted to float otherwise. Any `na` argument makes the result `na`.
 #[derive(Debug)]
-pub struct Math;
+pub struct Math {
+    rng: Cell<Rng>,
+    /// Generators of `math.random` calls given a seed, by call site.
+    seeded: RefCell<HashMap<CallSite, Rng>>,
+}
+
+impl Default for Math {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Math {
+    pub fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Self::with_seed(seed)
+    }
+
+    /// Create a [`Math`] library whose unseeded `math.random` calls are reproducible.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rng: Cell::new(Rng::new(seed)),
+            seeded: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn random(&self, context: &NativeCallContext, args: &[Dynamic]) -> RhaiResultOf<Dynamic> {
+        let n = match numbers(args)? {
+            Some(n) => n,
+            None => return Ok(Dynamic::NA),
+        };
+        let (min, max) = match n.len() {
+            0 => (0.0, 1.0),
+            1 => (0.0, n[0].to_float()),
+            _ => (n[0].to_float(), n[1].to_float()),
+        };
+        let unit = match n.get(2) {
+            Some(&Number::Int(seed)) => {
+                let mut seeded = self.seeded.borrow_mut();
+                let rng = seeded.entry(context.call_site()).or_insert_with(|| Rng::new(seed as u64));
+                rng.next()
+            }
+            Some(_) => return Err(ERR::ErrorMismatchDataType("int".into(), "float".into(), Position::NONE).into()),
+            None => {
+                let mut rng = self.rng.get();
+                let unit = rng.next();
+                self.rng.set(rng);
+                unit
+            }
+        };
+        Ok(Dynamic::Float(min + unit * (max - min)))
+    }
+}
 
 impl Library for Math {
-    fn call_method(&self, func_name: &str, args: Vec<Expression>) -> Result<Expression, String> {
+    fn call_method(&self, context: &NativeCallContext, func_name: &str, args: Vec<Dynamic>) -> RhaiResultOf<Dynamic> {
+        let arity = match func_name {
+            "pi" | "e" => (0, 0),
+            "random" => (0, 3),
+            "round" => (1, 2),
+            "pow" | "atan2" => (2, 2),
+            "min" | "max" | "avg" | "sum" => (1, usize::MAX),
+            "abs" | "floor" | "ceil" | "sqrt" | "exp" | "log" | "log10" | "sign" | "sin" | "cos"
+            | "tan" | "asin" | "acos" | "atan" | "todegrees" | "toradians" => (1, 1),
+            _ => return Err(ERR::ErrorFunctionNotFound(format!("math.{}", func_name), Position::NONE).into()),
+        };
+        check_arity("math", func_name, &args, arity.0, arity.1)?;
 
-        println!("fuck{:?},{:?}",func_name,args.get(0));
         match func_name {
-            "abs" => {
-                if let Some(Expression::Atom(Atom::Double(value))) = args.get(0) {
-                    Ok(Expression::Atom(Atom::Double(value.abs())))
-                } else {
-                    Err("Invalid argument for math.abs".to_string())
-                }
+            "pi" => return Ok(std::f64::consts::PI.into()),
+            "e" => return Ok(std::f64::consts::E.into()),
+            "random" => return self.random(context, &args),
+            _ => (),
+        }
+
+        let n = match numbers(&args)? {
+            Some(n) => n,
+            None => return Ok(Dynamic::NA),
+        };
+        let x = n[0].to_float();
+
+        Ok(match (func_name, n[0]) {
+            ("abs", Number::Int(i)) => i.checked_abs().map_or(Dynamic::NA, Dynamic::Int),
+            ("abs", _) => x.abs().into(),
+            ("sign", Number::Int(i)) => i.signum().into(),
+            ("sign", _) => float(if x == 0.0 { 0.0 } else { x.signum() }),
+            ("round", Number::Int(i)) if n.len() == 1 => i.into(),
+            ("round", _) if n.len() == 1 => to_int(x.round()),
+            ("round", _) => {
+                let precision = match n[1] {
+                    Number::Int(p) => p,
+                    Number::Float(_) => return Err(ERR::ErrorMismatchDataType("int".into(), "float".into(), Position::NONE).into()),
+                };
+                let scale = (10.0 as FLOAT).powi(precision.clamp(-308, 308) as i32);
+                float((x * scale).round() / scale)
             }
+            ("floor", Number::Int(i)) | ("ceil", Number::Int(i)) => i.into(),
+            ("floor", _) => to_int(x.floor()),
+            ("ceil", _) => to_int(x.ceil()),
+            ("sqrt", _) => float(x.sqrt()),
+            ("pow", _) => float(x.powf(n[1].to_float())),
+            ("exp", _) => float(x.exp()),
+            ("log", _) => float(x.ln()),
+            ("log10", _) => float(x.log10()),
+            ("sin", _) => float(x.sin()),
+            ("cos", _) => float(x.cos()),
+            ("tan", _) => float(x.tan()),
+            ("asin", _) => float(x.asin()),
+            ("acos", _) => float(x.acos()),
+            ("atan", _) => float(x.atan()),
+            ("atan2", _) => float(x.atan2(n[1].to_float())),
+            ("todegrees", _) => float(x.to_degrees()),
+            ("toradians", _) => float(x.to_radians()),
+            ("min", _) => fold(&n, INT::min, FLOAT::min).into(),
+            ("max", _) => fold(&n, INT::max, FLOAT::max).into(),
+            ("sum", _) => fold(&n, INT::wrapping_add, |a, b| a + b).into(),
+            ("avg", _) => float(n.iter().map(|n| n.to_float()).sum::<FLOAT>() / n.len() as FLOAT),
+            _ => unreachable!("arity checked above"),
+        })
+    }
+}
 
-            _ => Err(format!("Function {} not found in math library", func_name)),
-        }
+#[cfg(test)]
+mod tests {
+    use crate::series::bar::fixtures::closes;
+    use crate::types::Dynamic;
+    use crate::Engine;
+
+    fn eval(code: &str) -> Dynamic {
+        let result = Engine::new().run_ast(&format!("x = {}", code)).unwrap();
+        result["x"].clone()
+    }
+
+    #[test]
+    fn test_math_promotion() {
+        assert_eq!(eval("math.abs(-22)"), Dynamic::Int(22));
+        assert_eq!(eval("math.abs(-2.5)"), Dynamic::Float(2.5));
+        assert_eq!(eval("math.round(2.5)"), Dynamic::Int(3));
+        assert_eq!(eval("math.round(2.345, 2)"), Dynamic::Float(2.35));
+        assert_eq!(eval("math.floor(-1.5)"), Dynamic::Int(-2));
+        assert_eq!(eval("math.ceil(7)"), Dynamic::Int(7));
+        assert_eq!(eval("math.sqrt(16)"), Dynamic::Float(4.0));
+        assert_eq!(eval("math.pow(2, 10)"), Dynamic::Float(1024.0));
+        assert_eq!(eval("math.sign(-3)"), Dynamic::Int(-1));
+        assert_eq!(eval("math.sign(0.5)"), Dynamic::Float(1.0));
+        assert_eq!(eval("math.log(math.e)"), Dynamic::Float(1.0));
+        assert_eq!(eval("math.log10(1000)"), Dynamic::Float(3.0));
+        assert_eq!(eval("math.todegrees(math.pi)"), Dynamic::Float(180.0));
+        assert_eq!(eval("math.sqrt(-1)"), Dynamic::NA);
+        assert_eq!(eval("math.abs(na)"), Dynamic::NA);
+    }
+
+    #[test]
+    fn test_math_variadic() {
+        assert_eq!(eval("math.max(3, 9, 4)"), Dynamic::Int(9));
+        assert_eq!(eval("math.min(3, 1.5, 4)"), Dynamic::Float(1.5));
+        assert_eq!(eval("math.sum(1, 2, 3)"), Dynamic::Int(6));
+        assert_eq!(eval("math.avg(1, 2)"), Dynamic::Float(1.5));
+        assert_eq!(eval("math.max(1, na)"), Dynamic::NA);
+    }
+
+    #[test]
+    fn test_math_random() {
+        let a = eval("math.random(10, 20, 42)");
+        assert_eq!(a, eval("math.random(10, 20, 42)"));
+        assert_ne!(a, eval("math.random(10, 20, 43)"));
+        let x = a.as_float().unwrap();
+        assert!((10.0..20.0).contains(&x));
+
+        // a seeded call site continues its sequence, and each call of a function has its own
+        let code = "f() => math.random(0, 1, 7)\na = f()\nb = f()\n";
+        let result = Engine::new().run_series(code, &closes(&[1.0, 2.0])).unwrap();
+        assert_ne!(result["a"][0], result["a"][1]);
+        assert_eq!(result["a"][0], result["b"][0]);
+        assert_eq!(result["a"][1], result["b"][1]);
+    }
+
+    #[test]
+    fn test_math_errors() {
+        let err = Engine::new().run_ast("x = math.pow(2)").unwrap_err();
+        assert_eq!(err.to_string(), "math.pow expects 2 arguments but 1 were given (line 1, position 5)");
+        let err = Engine::new().run_ast("x = math.abs(\"a\")").unwrap_err();
+        assert_eq!(err.to_string(), "Data type incorrect: string (expecting number) (line 1, position 5)");
+        assert!(Engine::new().run_ast("x = math.nope(1)").is_err());
     }
-}
\ No newline at end of file
+}
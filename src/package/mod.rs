@@ -1,2 +1,3 @@
 pub mod math;
+pub mod string;
 pub mod lib;
\ No newline at end of file
@@ -0,0 +1,178 @@
+use crate::package::lib::{check_arity, Library, NativeCallContext};
+use crate::types::{Dynamic, Position};
+use crate::{Array, RhaiResultOf, ERR, FLOAT, INT};
+
+/// Get a string argument, or `None` if it is `na`.
+fn string(value: &Dynamic) -> RhaiResultOf<Option<&str>> {
+    match value {
+        Dynamic::Str(s) => Ok(Some(s)),
+        Dynamic::Na => Ok(None),
+        value => Err(ERR::ErrorMismatchDataType("string".into(), value.type_name().into(), Position::NONE).into()),
+    }
+}
+
+/// Replace each `{N}` placeholder in `format` with the `N`-th argument.
+fn format(format: &str, args: &[Dynamic]) -> RhaiResultOf<String> {
+    let mut result = String::with_capacity(format.len());
+    let mut rest = format;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = rest.find('}').unwrap_or(0);
+        match rest[1..end.max(1)].parse::<usize>() {
+            Ok(index) if end > 1 => {
+                let arg = args.get(index).ok_or_else(|| {
+                    format!("str.format refers to argument {{{}}} but {} were given", index, args.len())
+                })?;
+                result.push_str(&arg.to_string());
+                rest = &rest[end + 1..];
+            }
+            // Not a placeholder
+            _ => {
+                result.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Parse a number, or `na` if `s` is not one.
+fn to_number(s: &str) -> Dynamic {
+    match s.trim().parse::<FLOAT>() {
+        Ok(n) if n.is_finite() => Dynamic::Float(n),
+        _ => Dynamic::NA,
+    }
+}
+
+/// The `str` library.
+///
+/// String positions and lengths count characters, not bytes. Any `na` string argument
+/// makes the result `na`.
+///
+/// `str.tostring` also writes the values of `${expr}` in an interpolated `` `...` `` string.
+#[derive(Debug, Default)]
+pub struct Str;
+
+impl Str {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Library for Str {
+    fn call_method(&self, _context: &NativeCallContext, func_name: &str, args: Vec<Dynamic>) -> RhaiResultOf<Dynamic> {
+        let arity = match func_name {
+            "format" => (1, usize::MAX),
+            "tostring" | "length" | "upper" | "lower" | "tonumber" => (1, 1),
+            "contains" | "split" => (2, 2),
+            "replace" => (3, 4),
+            "replace_all" => (3, 3),
+            _ => return Err(ERR::ErrorFunctionNotFound(format!("str.{}", func_name), Position::NONE).into()),
+        };
+        check_arity("str", func_name, &args, arity.0, arity.1)?;
+
+        if func_name == "tostring" {
+            return Ok(args[0].to_string().into());
+        }
+
+        let s = match string(&args[0])? {
+            Some(s) => s,
+            None => return Ok(Dynamic::NA),
+        };
+        // Arguments after the first that are strings too
+        let count = match func_name {
+            "format" => 0,
+            "replace" => 2,
+            _ => args.len() - 1,
+        };
+        let mut strings = Vec::with_capacity(count);
+        for arg in &args[1..=count] {
+            match string(arg)? {
+                Some(s) => strings.push(s),
+                None => return Ok(Dynamic::NA),
+            }
+        }
+
+        Ok(match func_name {
+            "format" => format(s, &args[1..])?.into(),
+            "length" => (s.chars().count() as INT).into(),
+            "upper" => s.to_uppercase().into(),
+            "lower" => s.to_lowercase().into(),
+            "tonumber" => to_number(s),
+            "contains" => s.contains(strings[0]).into(),
+            "split" if strings[0].is_empty() => s.chars().map(|c| c.to_string().into()).collect::<Array>().into(),
+            "split" => s.split(strings[0]).map(|part| part.into()).collect::<Array>().into(),
+            "replace_all" => s.replace(strings[0], strings[1]).into(),
+            "replace" => {
+                let occurrence = match args.get(3) {
+                    None => 0,
+                    Some(Dynamic::Int(n)) if *n >= 0 => *n as usize,
+                    Some(value) => return Err(ERR::ErrorMismatchDataType("non-negative int".into(), value.type_name().into(), Position::NONE).into()),
+                };
+                match s.match_indices(strings[0]).nth(occurrence) {
+                    Some((start, found)) if !found.is_empty() => {
+                        format!("{}{}{}", &s[..start], strings[1], &s[start + found.len()..]).into()
+                    }
+                    _ => s.into(),
+                }
+            }
+            _ => unreachable!("arity checked above"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Dynamic;
+    use crate::Engine;
+
+    fn eval(code: &str) -> Dynamic {
+        let result = Engine::new().run_ast(&format!("x = {}", code)).unwrap();
+        result["x"].clone()
+    }
+
+    #[test]
+    fn test_str_concatenation() {
+        assert_eq!(eval("\"a\" + 'b'"), Dynamic::from("ab"));
+        assert_eq!(eval("\"n=\" + 5 + \", f=\" + 1.5"), Dynamic::from("n=5, f=1.5"));
+        assert_eq!(eval("2 + \"x\""), Dynamic::from("2x"));
+        assert_eq!(eval("\"a\" + na"), Dynamic::NA);
+        assert!(Engine::new().run_ast("x = \"a\" + true").is_err());
+    }
+
+    #[test]
+    fn test_str_interpolation() {
+        assert_eq!(eval("`n=${1 + 2}, ok=${true}, v=${na}`"), Dynamic::from("n=3, ok=true, v=na"));
+        assert_eq!(eval("`${'a'}${\"b\"}`"), Dynamic::from("ab"));
+        assert_eq!(eval("`cost \\${x} {0}`"), Dynamic::from("cost ${x} {0}"));
+        assert_eq!(eval("``"), Dynamic::from(""));
+        assert_eq!(eval("tostring(5) + tostring(1.5)"), Dynamic::from("51.5"));
+    }
+
+    #[test]
+    fn test_str_functions() {
+        assert_eq!(eval("str.format(\"{0} is {1}, {0}! {x}\", \"close\", 1.5)"), Dynamic::from("close is 1.5, close! {x}"));
+        assert_eq!(eval("str.tostring(42) + str.tostring(na)"), Dynamic::from("42na"));
+        assert_eq!(eval("str.length(\"héllo\")"), Dynamic::Int(5));
+        assert_eq!(eval("str.contains(\"abc\", \"bc\")"), Dynamic::TRUE);
+        assert_eq!(eval("str.replace(\"a-b-c\", \"-\", \"+\", 1)"), Dynamic::from("a-b+c"));
+        assert_eq!(eval("str.replace_all(\"a-b-c\", \"-\", \"+\")"), Dynamic::from("a+b+c"));
+        assert_eq!(eval("str.split(\"a,b\", \",\")"), Dynamic::from(vec![Dynamic::from("a"), Dynamic::from("b")]));
+        assert_eq!(eval("str.upper(\"abc\") + str.lower(\"DEF\")"), Dynamic::from("ABCdef"));
+        assert_eq!(eval("str.tonumber(\" 3.5 \")"), Dynamic::Float(3.5));
+        assert_eq!(eval("str.tonumber(\"x\")"), Dynamic::NA);
+        assert_eq!(eval("str.length(na)"), Dynamic::NA);
+    }
+
+    #[test]
+    fn test_str_errors() {
+        let err = Engine::new().run_ast("x = str.format(\"{2}\", 1)").unwrap_err();
+        assert_eq!(err.to_string(), "str.format refers to argument {2} but 1 were given (line 1, position 5)");
+        let err = Engine::new().run_ast("x = str.length(1)").unwrap_err();
+        assert_eq!(err.to_string(), "Data type incorrect: int (expecting string) (line 1, position 5)");
+    }
+}
@@ -1,13 +1,14 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_until};
-use nom::character::complete::{alpha1, multispace0, i64, space0, char};
-use nom::combinator::{map, opt};
+use nom::bytes::complete::{is_not, tag, take_until, take_while, take_while_m_n};
+use nom::character::complete::{anychar, char, digit1, line_ending, multispace1, one_of, satisfy, space0, space1};
+use nom::combinator::{consumed, eof, map, not, opt, peek, recognize};
+use nom::error::ErrorKind;
 use nom::multi::{many0, separated_list0};
-use nom::number::complete::double;
-use nom::sequence::{delimited, preceded, terminated, tuple};
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
 use nom::IResult;
+use nom_locate::LocatedSpan;
+use crate::ast::annotation::Annotation;
+use crate::ast::ast::AST;
 use crate::ast::atom::Atom;
 use crate::ast::binaryop::BinaryOperation;
 use crate::ast::block::Block;
@@ -15,245 +16,1027 @@ use crate::ast::expression::Expression;
 use crate::ast::function::Function;
 use crate::ast::parameter::Parameter;
 use crate::ast::stmt::Statement;
+use crate::ast::unaryop::UnaryOperation;
+use crate::types::{ParseError, ParseErrorType, Position, Span};
 
+/// Parser input, tracking the line and column of every slice of the script.
+pub type Input<'a> = LocatedSpan<&'a str>;
 
-fn parse_identifier(input: &str) -> IResult<&str, String> {
-    map(alpha1, |s: &str| s.to_string())(input)
+type PResult<'a, O> = IResult<Input<'a>, O, SyntaxError<'a>>;
+
+/// Error produced by the parsers.
+///
+/// Without a `kind` the error is an ordinary mismatch that alternatives may recover from;
+/// with a `kind` it is a real syntax error that is reported to the user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError<'a> {
+    input: Input<'a>,
+    kind: Option<ParseErrorType>,
+}
+
+impl<'a> SyntaxError<'a> {
+    fn new(input: Input<'a>, kind: ParseErrorType) -> Self {
+        Self { input, kind: Some(kind) }
+    }
+
+    /// Convert into a [`ParseError`], describing plain mismatches by the input found there.
+    fn into_parse_error(self) -> ParseError {
+        let pos = position(&self.input);
+        match self.kind {
+            Some(kind) => kind.into_err(pos),
+            None if self.input.fragment().is_empty() => ParseErrorType::UnexpectedEOF.into_err(pos),
+            None => ParseErrorType::UnexpectedInput(next_token(&self.input)).into_err(pos),
+        }
+    }
+}
+
+impl<'a> nom::error::ParseError<Input<'a>> for SyntaxError<'a> {
+    fn from_error_kind(input: Input<'a>, _kind: ErrorKind) -> Self {
+        Self { input, kind: None }
+    }
+
+    fn append(_input: Input<'a>, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    /// Keep whichever alternative got further into the input, as it is the more precise.
+    fn or(self, other: Self) -> Self {
+        if other.input.location_offset() >= self.input.location_offset() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+/// Get the [`Position`] of the start of `input`.
+fn position(input: &Input) -> Position {
+    Position::new(input.location_line(), input.get_utf8_column() as u32)
+}
+
+/// The word or symbol at the start of `input`, for use in error messages.
+fn next_token(input: &Input) -> String {
+    let text = input.fragment();
+    let word = text
+        .find(|c: char| !is_id_continue(c))
+        .unwrap_or(text.len());
+
+    match text.chars().next() {
+        Some(_) if word > 0 => text[..word].to_string(),
+        Some(c) => c.to_string(),
+        None => String::new(),
+    }
+}
+
+/// Run `parser`, turning a mismatch into a syntax error described by `err`.
+///
+/// Use this once the input can no longer be anything other than the construct being parsed.
+fn expect<'a, O, F, E>(mut parser: F, err: E) -> impl FnMut(Input<'a>) -> PResult<'a, O>
+where
+    F: FnMut(Input<'a>) -> PResult<'a, O>,
+    E: Fn() -> ParseErrorType,
+{
+    move |input| match parser(input) {
+        Err(nom::Err::Error(e)) if e.kind.is_none() => {
+            Err(nom::Err::Failure(SyntaxError::new(input, err())))
+        }
+        Err(nom::Err::Error(e)) => Err(nom::Err::Failure(e)),
+        result => result,
+    }
+}
+
+/// Words that cannot be used as names.
+const KEYWORDS: &[&str] = &[
+    "and", "break", "by", "continue", "else", "export", "false", "for", "if", "import", "in", "na",
+    "not", "or", "switch", "to", "true", "var", "varip", "while",
+];
+
+/// Is `name` a reserved keyword?
+pub fn is_keyword(name: &str) -> bool {
+    KEYWORDS.contains(&name)
+}
+
+/// Can `c` start an identifier?
+#[cfg(not(feature = "unicode-xid-ident"))]
+fn is_id_first(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+/// Can `c` appear in an identifier after its first character?
+#[cfg(not(feature = "unicode-xid-ident"))]
+fn is_id_continue(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Can `c` start an identifier?
+#[cfg(feature = "unicode-xid-ident")]
+fn is_id_first(c: char) -> bool {
+    unicode_xid::UnicodeXID::is_xid_start(c) || c == '_'
+}
+
+/// Can `c` appear in an identifier after its first character?
+#[cfg(feature = "unicode-xid-ident")]
+fn is_id_continue(c: char) -> bool {
+    unicode_xid::UnicodeXID::is_xid_continue(c)
+}
+
+/// An identifier, which may also be a keyword.
+fn parse_identifier(input: Input) -> PResult<String> {
+    map(
+        recognize(pair(satisfy(is_id_first), take_while(is_id_continue))),
+        |s: Input| s.fragment().to_string(),
+    )(input)
 }
 
-fn parse_parameter(input: &str) -> IResult<&str, Parameter> {
+/// Reject a reserved keyword, found at `input`, used as the name of a variable, function or parameter.
+fn check_name<'a>(input: Input<'a>, name: &str) -> Result<(), nom::Err<SyntaxError<'a>>> {
+    if is_keyword(name) {
+        Err(nom::Err::Failure(SyntaxError::new(input, ParseErrorType::Reserved(name.to_string()))))
+    } else {
+        Ok(())
+    }
+}
+
+fn parse_parameter(input: Input) -> PResult<Parameter> {
+    let pos = position(&input);
     let (input, name) = parse_identifier(input)?;
-    let (input, default_value) = opt(preceded(tag("="), parse_expression))(input)?;
-    Ok((input, Parameter { name, default_value }))
+    let (input, default_value) = opt(preceded(delimited(blanks, tag("="), blanks), parse_expression))(input)?;
+    Ok((input, Parameter { name, default_value, pos }))
 }
 
-fn parse_parameter_list(input: &str) -> IResult<&str, Vec<Parameter>> {
+/// Parse the parameters of a function definition, each with the input it starts at.
+fn parse_parameter_list(input: Input) -> PResult<Vec<(Input, Parameter)>> {
     delimited(
         tag("("),
         separated_list0(
-            delimited(multispace0, tag(","), multispace0),
-            parse_parameter,
+            delimited(whitespace, tag(","), whitespace),
+            consumed(parse_parameter),
         ),
         tag(")")
     )(input)
 }
 
-fn parse_block(input: &str) -> IResult<&str, Block> {
-    let (input, statements) = many0(preceded(multispace0, parse_statement))(input)?;
-    let (input, _) = multispace0(input)?;
+fn parse_single_line_body(input: Input) -> PResult<Block> {
+    let start = position(&input);
     let (input, return_expr) = parse_expression(input)?;
-    Ok((input, Block { statements, return_expr }))
+    let span = Span::new(start, position(&input));
+    Ok((input, Block { statements: Vec::new(), return_expr: Some(return_expr), span }))
 }
 
-fn parse_single_line_body(input: &str) -> IResult<&str, Block> {
-    let (input, return_expr) = parse_expression(input)?;
-    Ok((input, Block { statements: Vec::new(), return_expr }))
+/// Parse a function body: an expression on the same line as `=>`, or an indented block
+/// deeper than the line of the definition.
+fn parse_function_body(input: Input, indent: usize) -> PResult<Block> {
+    alt((
+        parse_single_line_body,
+        |input| parse_indented_block(input, indent),
+    ))(input)
 }
 
-fn parse_function_body(input: &str) -> IResult<&str, Block> {
+/// Width of the indentation of the line `input` is on.
+fn line_indent(input: &Input) -> usize {
+    let line = input.get_line_beginning();
+    line.iter().take_while(|&&c| c == b' ' || c == b'\t').count()
+}
+
+/// Skip any lines at `input` holding nothing but blanks and comments; `input` must be at the
+/// start of a line.
+fn skip_blank_lines(mut input: Input) -> Input {
+    while !input.fragment().is_empty() {
+        match terminated(blanks, alt((line_ending::<_, SyntaxError>, eof)))(input) {
+            Ok((rest, _)) => input = rest,
+            Err(_) => break,
+        }
+    }
+    input
+}
+
+/// A `//` comment running to the end of the line, or a `/* */` comment.
+fn parse_comment(input: Input) -> PResult<Input> {
     alt((
-        preceded(
-            delimited(space0, tag("=>"), space0),
-            parse_single_line_body,
-        ),
-        delimited(
-            delimited(space0, tag("=>"), space0),
-            parse_block,
-            multispace0
-        )
+        recognize(pair(tag("//"), opt(is_not("\r\n")))),
+        parse_block_comment,
     ))(input)
 }
 
-fn parse_variable_declaration(input: &str) -> IResult<&str, Statement> {
+fn parse_block_comment(input: Input) -> PResult<Input> {
+    let (rest, _) = tag("/*")(input)?;
+    match take_until::<_, _, SyntaxError>("*/")(rest) {
+        Ok((rest, _)) => recognize(tag("*/"))(rest).map(|(rest, _)| (rest, input)),
+        Err(_) => Err(nom::Err::Failure(SyntaxError::new(input, ParseErrorType::UnterminatedComment))),
+    }
+}
+
+/// Blanks and comments within a line.
+fn blanks(input: Input) -> PResult<Input> {
+    recognize(many0(alt((space1, parse_comment))))(input)
+}
+
+/// Blanks, line breaks and comments.
+fn whitespace(input: Input) -> PResult<Input> {
+    recognize(many0(alt((multispace1, parse_comment))))(input)
+}
+
+/// An annotation comment, `//@name=value` or `//@name value`.
+fn parse_annotation(input: Input) -> PResult<Annotation> {
+    let pos = position(&input);
+    let (input, name) = preceded(tag("//@"), parse_identifier)(input)?;
+    let (input, text) = opt(is_not("\r\n"))(input)?;
+    let value = text.map_or("", |text| *text.fragment()).trim();
+    let value = value.strip_prefix('=').unwrap_or(value).trim_start();
+    Ok((input, Annotation { name, value: value.to_string(), pos }))
+}
+
+/// Skip the whitespace and comments between top-level statements, collecting annotations.
+fn parse_top_level_space(input: Input) -> PResult<Vec<Annotation>> {
+    let (input, found) = many0(alt((
+        map(multispace1, |_| None),
+        map(parse_annotation, Some),
+        map(parse_comment, |_| None),
+    )))(input)?;
+    Ok((input, found.into_iter().flatten().collect()))
+}
+
+/// Parse the statements of an indented block, one per line, following the header line at `input`.
+///
+/// The block ends before the first line indented no deeper than `parent_indent`, leaving
+/// `input` at the end of the block's last line.
+fn parse_indented_block(input: Input, parent_indent: usize) -> PResult<Block> {
+    let (input, _) = blanks(input)?;
+    let (input, _) = line_ending::<_, SyntaxError>(input)?;
+    let mut input = skip_blank_lines(input);
+    let start = position(&input);
+    let (_, indent) = space0(input)?;
+    if indent.fragment().len() <= parent_indent {
+        return Err(nom::Err::Error(SyntaxError { input, kind: None }));
+    }
+
+    let mut statements = Vec::new();
+    loop {
+        let (rest, _) = space0(input)?;
+        let (rest, statement) = parse_statement(rest)?;
+        statements.push(statement);
+
+        let (after, _) = parse_end_of_line(rest)?;
+        let next = skip_blank_lines(after);
+        if next.fragment().is_empty() {
+            input = rest;
+            break;
+        }
+
+        let (token, next_indent) = space0(next)?;
+        let (block, line) = (*indent.fragment(), *next_indent.fragment());
+        if line == block {
+            input = next;
+        } else if block.starts_with(line) && line.len() <= parent_indent {
+            // Back to an enclosing block, which checks the indentation further
+            input = rest;
+            break;
+        } else if line.starts_with(block) {
+            return Err(nom::Err::Failure(SyntaxError::new(token, ParseErrorType::UnexpectedIndent)));
+        } else {
+            return Err(nom::Err::Failure(SyntaxError::new(token, ParseErrorType::InconsistentIndent)));
+        }
+    }
+
+    // The final expression of the block is its value
+    let return_expr = match statements.pop() {
+        Some(Statement::Expression(expr)) => Some(expr),
+        Some(Statement::FunctionCall(name, args, named, pos)) => Some(Expression::FunctionCall(name, args, named, pos)),
+        Some(statement) => {
+            statements.push(statement);
+            None
+        }
+        None => None,
+    };
+    let span = Span::new(start, position(&input));
+    Ok((input, Block { statements, return_expr, span }))
+}
+
+fn parse_if(input: Input) -> PResult<Expression> {
+    let pos = position(&input);
+    let indent = line_indent(&input);
+    let (input, _) = terminated(keyword("if"), blanks)(input)?;
+    let (input, condition) = expect(parse_expression, || ParseErrorType::ExprExpected("after `if`".into()))(input)?;
+    let (input, then_block) = expect(
+        |input| parse_indented_block(input, indent),
+        || ParseErrorType::BlockExpected("after `if` condition".into()),
+    )(input)?;
+    let (input, else_block) = opt(|input| parse_else(input, indent))(input)?;
+    Ok((input, Expression::If(Box::new(condition), Box::new(then_block), else_block.map(Box::new), pos)))
+}
+
+fn parse_for(input: Input) -> PResult<Expression> {
+    let pos = position(&input);
+    let indent = line_indent(&input);
+    let (start, _) = terminated(keyword("for"), blanks)(input)?;
+    let (input, var) = expect(parse_identifier, || ParseErrorType::MissingToken("variable".into(), "after `for`".into()))(start)?;
+    check_name(start, &var)?;
+    let (input, _) = blanks(input)?;
+
+    if let Ok((input, _)) = terminated(keyword("in"), blanks)(input) {
+        let (input, iterable) = expect(parse_expression, || ParseErrorType::ExprExpected("after `in`".into()))(input)?;
+        let (input, body) = parse_loop_body(input, indent)?;
+        return Ok((input, Expression::ForIn(var, Box::new(iterable), Box::new(body), pos)));
+    }
+
+    let (input, _) = terminated(
+        expect(tag("="), || ParseErrorType::MissingToken("=".into(), "or `in` after the loop variable".into())),
+        blanks,
+    )(input)?;
+    let (input, from) = expect(parse_expression, || ParseErrorType::ExprExpected("after `=`".into()))(input)?;
+    let (input, _) = delimited(
+        blanks,
+        expect(keyword("to"), || ParseErrorType::MissingToken("to".into(), "after the initial value".into())),
+        blanks,
+    )(input)?;
+    let (input, to) = expect(parse_expression, || ParseErrorType::ExprExpected("after `to`".into()))(input)?;
+    let (input, step) = opt(preceded(
+        delimited(blanks, keyword("by"), blanks),
+        expect(parse_expression, || ParseErrorType::ExprExpected("after `by`".into())),
+    ))(input)?;
+    let (input, body) = parse_loop_body(input, indent)?;
+    Ok((input, Expression::For(var, Box::new(from), Box::new(to), step.map(Box::new), Box::new(body), pos)))
+}
+
+fn parse_while(input: Input) -> PResult<Expression> {
+    let pos = position(&input);
+    let indent = line_indent(&input);
+    let (input, _) = terminated(keyword("while"), blanks)(input)?;
+    let (input, condition) = expect(parse_expression, || ParseErrorType::ExprExpected("after `while`".into()))(input)?;
+    let (input, body) = parse_loop_body(input, indent)?;
+    Ok((input, Expression::While(Box::new(condition), Box::new(body), pos)))
+}
+
+fn parse_loop_body(input: Input, indent: usize) -> PResult<Block> {
+    expect(
+        |input| parse_indented_block(input, indent),
+        || ParseErrorType::BlockExpected("for the loop body".into()),
+    )(input)
+}
+
+/// Parse an `else` or `else if` branch on the line after an `if` block, aligned with its `if`.
+fn parse_else(input: Input, indent: usize) -> PResult<Block> {
+    let (input, _) = preceded(blanks, line_ending::<_, SyntaxError>)(input)?;
+    let input = skip_blank_lines(input);
+    let (input, leading) = space0(input)?;
+    if leading.fragment().len() != indent {
+        return Err(nom::Err::Error(SyntaxError { input, kind: None }));
+    }
+    let (input, _) = terminated(keyword("else"), blanks)(input)?;
+
+    match parse_if(input) {
+        Ok((rest, expr)) => {
+            let span = Span::new(expr.position(), position(&rest));
+            Ok((rest, Block { statements: Vec::new(), return_expr: Some(expr), span }))
+        }
+        Err(nom::Err::Error(_)) => expect(
+            |input| parse_indented_block(input, indent),
+            || ParseErrorType::BlockExpected("after `else`".into()),
+        )(input),
+        Err(err) => Err(err),
+    }
+}
+
+fn parse_variable_declaration(input: Input) -> PResult<Statement> {
+    let (start, pos) = (input, position(&input));
     let (input, name) = parse_identifier(input)?;
-    let (input, _) = space0(input)?;
-    let (input, _) = tag("=")(input)?;
-    let (input, _) = space0(input)?;
-    let (input, expr) = parse_expression(input)?;
-    Ok((input, Statement::VariableDeclaration(name, expr)))
+    let (input, _) = blanks(input)?;
+    let (input, _) = terminated(tag("="), not(one_of("=>")))(input)?;
+    check_name(start, &name)?;
+    let (input, _) = blanks(input)?;
+    let (input, expr) = expect(parse_expression, || ParseErrorType::ExprExpected("after `=`".into()))(input)?;
+    Ok((input, Statement::VariableDeclaration(name, expr, pos)))
+}
+
+/// A variable kept from one bar to the next, `var x = init`.
+///
+/// `varip` is accepted as well; as bars are only ever run once, it behaves just like `var`.
+fn parse_persistent_declaration(input: Input) -> PResult<Statement> {
+    let pos = position(&input);
+    let (input, word) = terminated(alt((keyword("varip"), keyword("var"))), blanks)(input)?;
+    let start = input;
+    let (input, name) = expect(parse_identifier, || ParseErrorType::MissingToken("variable".into(), format!("after `{}`", word)))(input)?;
+    check_name(start, &name)?;
+    let (input, _) = preceded(blanks, expect(tag("="), || ParseErrorType::MissingToken("=".into(), "after the variable name".into())))(input)?;
+    let (input, _) = blanks(input)?;
+    let (input, expr) = expect(parse_expression, || ParseErrorType::ExprExpected("after `=`".into()))(input)?;
+    Ok((input, Statement::PersistentDeclaration(name, expr, pos)))
 }
 
-fn parse_assignment(input: &str) -> IResult<&str, Statement> {
+/// Reassignment of a variable declared earlier, `x := value`, or a compound assignment such as
+/// `x += value`, which stands for `x := x + value`.
+fn parse_assignment(input: Input) -> PResult<Statement> {
+    let (start, pos) = (input, position(&input));
     let (input, name) = parse_identifier(input)?;
-    let (input, _) = space0(input)?;
-    let (input, _) = tag("=")(input)?;
-    let (input, _) = space0(input)?;
-    let (input, expr) = parse_expression(input)?;
-    Ok((input, Statement::Assignment(name, expr)))
+    let (input, _) = blanks(input)?;
+    let (input, op) = alt((tag(":="), tag("+="), tag("-="), tag("*="), tag("/=")))(input)?;
+    check_name(start, &name)?;
+    let (input, _) = blanks(input)?;
+    let (input, expr) = expect(parse_expression, || ParseErrorType::ExprExpected(format!("after `{}`", op)))(input)?;
+    let op_pos = position(&op);
+    let op = match *op.fragment() {
+        "+=" => BinaryOperation::Plus,
+        "-=" => BinaryOperation::Minus,
+        "*=" => BinaryOperation::Times,
+        "/=" => BinaryOperation::Divide,
+        _ => return Ok((input, Statement::Assignment(name, expr, pos))),
+    };
+    let current = Expression::Atom(Atom::Variable(name.clone()), pos);
+    let expr = Expression::BinaryOperation(op, Box::new(current), Box::new(expr), op_pos);
+    Ok((input, Statement::Assignment(name, expr, pos)))
 }
 
-fn parse_tuple_assignment(input: &str) -> IResult<&str, Statement> {
+fn parse_tuple_assignment(input: Input) -> PResult<Statement> {
+    let pos = position(&input);
     let (input, vars) = delimited(
         tag("["),
         separated_list0(
-            delimited(multispace0, tag(","), multispace0),
-            parse_identifier
+            delimited(whitespace, tag(","), whitespace),
+            consumed(parse_identifier)
         ),
         tag("]")
     )(input)?;
-    let (input, _) = space0(input)?;
-    let (input, _) = tag("=")(input)?;
-    let (input, _) = space0(input)?;
-    let (input, expr) = parse_expression(input)?;
-    Ok((input, Statement::TupleAssignment(vars, expr)))
+    let (input, _) = blanks(input)?;
+    let (input, _) = terminated(tag("="), not(one_of("=>")))(input)?;
+    for (start, name) in &vars {
+        check_name(*start, name)?;
+    }
+    let vars = vars.into_iter().map(|(_, name)| name).collect();
+    let (input, _) = blanks(input)?;
+    let (input, expr) = expect(parse_expression, || ParseErrorType::ExprExpected("after `=`".into()))(input)?;
+    Ok((input, Statement::TupleAssignment(vars, expr, pos)))
 }
 
 
-fn parse_function_definition(input: &str) -> IResult<&str, Statement> {
+fn parse_function_definition(input: Input) -> PResult<Statement> {
     let (input, function) = parse_function(input)?;
     Ok((input, Statement::FunctionDefinition(function)))
 }
 
 
-fn parse_function_call_statement(input: &str) -> IResult<&str, Statement> {
+fn parse_function_call_statement(input: Input) -> PResult<Statement> {
+    let pos = position(&input);
     let (input, func_name) = parse_identifier(input)?;
-    let (input, args) = delimited(
-        tag("("),
-        separated_list0(
-            delimited(multispace0, tag(","), multispace0),
-            parse_expression
-        ),
-        tag(")")
-    )(input)?;
-    Ok((input, Statement::FunctionCall(func_name, args)))
+    let (input, (args, named)) = parse_call_arguments(input)?;
+    // A call followed by more of an expression, as in `f(x) + 1`, is an expression statement
+    let (input, _) = peek(pair(blanks, alt((line_ending, eof))))(input)?;
+    Ok((input, Statement::FunctionCall(func_name, args, named, pos)))
+}
+
+
+
+
+/// Parse a whole script into its top-level statements.
+pub fn parse_script(code: &str) -> Result<Vec<Statement>, Vec<ParseError>> {
+    parse_ast(code).map(|ast| ast.statements)
 }
 
+/// Parse a whole script, along with the annotations in its comments.
+///
+/// After a syntax error, parsing resumes at the next line that does not belong to the failed
+/// statement, so that every error in the script is reported at once.
+pub fn parse_ast(code: &str) -> Result<AST, Vec<ParseError>> {
+    let mut input = Input::new(code);
+    let mut statements = Vec::new();
+    let mut annotations = Vec::new();
+    let mut errors = Vec::new();
+    let mut top_indent = None;
 
+    loop {
+        match parse_top_level_space(input) {
+            Ok((rest, found)) => {
+                annotations.extend(found);
+                input = rest;
+            }
+            Err(nom::Err::Error(err)) | Err(nom::Err::Failure(err)) => {
+                errors.push(err.into_parse_error());
+                break;
+            }
+            Err(nom::Err::Incomplete(_)) => unreachable!("complete parsers never ask for more input"),
+        }
+        if input.fragment().is_empty() {
+            break;
+        }
 
+        // All top-level statements share the indentation of the first one, which is made of the
+        // blanks starting the line and not of any comment before the statement
+        let indent: Vec<u8> = input.get_line_beginning()[..input.get_column() - 1].iter()
+            .take_while(|&&c| c == b' ' || c == b'\t')
+            .copied()
+            .collect();
+        let expected = top_indent.get_or_insert_with(|| indent.clone());
+        if indent != *expected {
+            let kind = if indent.starts_with(expected) {
+                ParseErrorType::UnexpectedIndent
+            } else {
+                ParseErrorType::InconsistentIndent
+            };
+            errors.push(kind.into_err(position(&input)));
+            input = skip_statement(input);
+            continue;
+        }
+
+        match terminated(parse_statement, parse_end_of_line)(input) {
+            Ok((rest, statement)) => {
+                statements.push(statement);
+                input = rest;
+            }
+            Err(nom::Err::Error(err)) | Err(nom::Err::Failure(err)) => {
+                let err = err.into_parse_error();
+                // Statements spanning several lines may hit the same error again on recovery
+                if errors.last() != Some(&err) {
+                    errors.push(err);
+                }
+                input = skip_statement(input);
+            }
+            Err(nom::Err::Incomplete(_)) => unreachable!("complete parsers never ask for more input"),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(AST { statements, annotations })
+    } else {
+        Err(errors)
+    }
+}
+
+/// Only blanks may follow a statement on its line.
+fn parse_end_of_line(input: Input) -> PResult<()> {
+    let (input, _) = blanks(input)?;
+    match alt((line_ending::<_, SyntaxError>, eof))(input) {
+        Ok((rest, _)) => Ok((rest, ())),
+        Err(_) => Err(nom::Err::Failure(SyntaxError::new(
+            input,
+            ParseErrorType::UnexpectedInput(next_token(&input)),
+        ))),
+    }
+}
 
-pub fn parse_statement(input: &str) -> IResult<&str, Statement> {
+/// Skip the line at `input`.
+fn skip_line(input: Input) -> Input {
+    let (rest, _) = opt(is_not::<_, _, SyntaxError>("\n"))(input).expect("opt never fails");
+    let (rest, _) = opt(char::<_, SyntaxError>('\n'))(rest).expect("opt never fails");
+    rest
+}
+
+/// Skip the statement starting at `input`: its own line plus any following lines that are
+/// blank or indented deeper, since those continue the same statement.
+fn skip_statement(input: Input) -> Input {
+    let indent = input.get_utf8_column() - 1;
+    let mut rest = skip_line(input);
+
+    loop {
+        let line = rest.fragment().split('\n').next().unwrap_or_default();
+        let line_indent = line.len() - line.trim_start().len();
+
+        if rest.fragment().is_empty() || (!line.trim().is_empty() && line_indent <= indent) {
+            return rest;
+        }
+        rest = skip_line(rest);
+    }
+}
+
+pub fn parse_statement(input: Input) -> PResult<Statement> {
     alt((
+        map(keyword("break"), |word| Statement::Break(position(&word))),
+        map(keyword("continue"), |word| Statement::Continue(position(&word))),
+        map(alt((parse_if, parse_for, parse_while)), Statement::Expression),
+        parse_persistent_declaration,
         parse_variable_declaration,
         parse_assignment,
         parse_tuple_assignment,
         parse_function_definition,
         parse_function_call_statement, // Ensure function calls are parsed
-
+        map(parse_expression, Statement::Expression),
     ))(input)
 }
 
-fn parse_function(input: &str) -> IResult<&str, Function> {
-    let (input, name) = parse_identifier(input)?;
+fn parse_function(input: Input) -> PResult<Function> {
+    let (rest, name) = parse_identifier(input)?;
+    parse_function_rest(rest, input, name)
+}
+
+/// An anonymous function, `(x, y) => x + y`.
+fn parse_lambda(input: Input) -> PResult<Function> {
+    parse_function_rest(input, input, String::new())
+}
+
+/// Parse the parameters and body of a function whose definition begins at `start` with `name`,
+/// which is empty for an anonymous function.
+fn parse_function_rest<'a>(input: Input<'a>, start: Input<'a>, name: String) -> PResult<'a, Function> {
+    let indent = line_indent(&start);
     let (input, parameters) = parse_parameter_list(input)?;
-    let (input, body) = parse_function_body(input)?;
-    Ok((input, Function { name, parameters, body }))
+    let (input, _) = delimited(blanks, tag("=>"), blanks)(input)?;
+
+    if !name.is_empty() {
+        check_name(start, &name)?;
+    }
+    for (param_input, param) in &parameters {
+        check_name(*param_input, &param.name)?;
+    }
+    let parameters = parameters.into_iter().map(|(_, param)| param).collect();
+    let (input, body) = expect(
+        |input| parse_function_body(input, indent),
+        || ParseErrorType::FnMissingBody(if name.is_empty() { "<anonymous>".into() } else { name.clone() }),
+    )(input)?;
+    let span = Span::new(position(&start), body.span.end());
+    Ok((input, Function { name, parameters, body, span }))
+}
+
+/// A string literal in double or single quotes, which must close on the same line.
+fn parse_string(input: Input) -> PResult<Atom> {
+    let (mut rest, quote) = one_of("\"'")(input)?;
+    let mut value = String::new();
+
+    loop {
+        match anychar::<_, SyntaxError>(rest) {
+            Ok((after, c)) if c == quote => return Ok((after, Atom::String(value))),
+            Ok((_, '\\')) => match parse_escape(rest) {
+                Ok((after, c)) => {
+                    value.push(c);
+                    rest = after;
+                }
+                Err(nom::Err::Error(_)) => break,
+                Err(err) => return Err(err),
+            },
+            Ok((after, c)) if c != '\n' && c != '\r' => {
+                value.push(c);
+                rest = after;
+            }
+            _ => break,
+        }
+    }
+    Err(nom::Err::Failure(SyntaxError::new(input, ParseErrorType::UnterminatedString)))
 }
 
-fn parse_string(input: &str) -> IResult<&str, Atom> {
-    let (input, s) = delimited(tag("\""), take_until("\""), tag("\""))(input)?;
-    Ok((input, Atom::String(s.to_string())))
+/// An escape sequence in a string literal: `\n`, `\r`, `\t`, `\0`, `\\`, `\"`, `\'`, `` \` ``,
+/// `\$` or a Unicode code point such as `\u{20AC}`.
+fn parse_escape(input: Input) -> PResult<char> {
+    let (rest, _) = char('\\')(input)?;
+    let malformed = |rest: Input| {
+        let len = rest.location_offset() - input.location_offset();
+        let text = input.fragment().get(..len).unwrap_or_default().to_string();
+        nom::Err::Failure(SyntaxError::new(input, ParseErrorType::MalformedEscapeSequence(text)))
+    };
+
+    let (rest, c) = match anychar::<_, SyntaxError>(rest) {
+        Ok((rest, 'n')) => (rest, '\n'),
+        Ok((rest, 'r')) => (rest, '\r'),
+        Ok((rest, 't')) => (rest, '\t'),
+        Ok((rest, '0')) => (rest, '\0'),
+        Ok((rest, c @ ('\\' | '"' | '\'' | '`' | '$'))) => (rest, c),
+        Ok((rest, 'u')) => {
+            let (rest, code) = delimited(char('{'), take_while_m_n(1, 6, |c: char| c.is_ascii_hexdigit()), char('}'))(rest)
+                .map_err(|_: nom::Err<SyntaxError>| malformed(rest))?;
+            let c = u32::from_str_radix(code.fragment(), 16).ok().and_then(char::from_u32).ok_or_else(|| malformed(rest))?;
+            (rest, c)
+        }
+        Ok((rest, c)) if c != '\n' && c != '\r' => return Err(malformed(rest)),
+        // A line break or the end of the script: the string is unterminated
+        _ => return Err(nom::Err::Error(SyntaxError { input, kind: None })),
+    };
+    Ok((rest, c))
 }
 
-fn parse_boolean(input: &str) -> IResult<&str, Atom> {
+/// An interpolated string in backticks, such as `` `close is ${close}` ``, which must close on
+/// the same line.
+///
+/// Each `${expr}` is replaced by the value of `expr` as [`str.tostring`][crate::package::string::Str]
+/// writes it, so the string becomes a concatenation of its text and those values.
+fn parse_interpolated_string(input: Input) -> PResult<Expression> {
+    let pos = position(&input);
+    let (mut rest, _) = char('`')(input)?;
+    let mut text = String::new();
+    let mut parts = Vec::new();
+
+    loop {
+        match anychar::<_, SyntaxError>(rest) {
+            Ok((after, '`')) => {
+                if !text.is_empty() || parts.is_empty() {
+                    parts.push(Expression::Atom(Atom::String(text), pos));
+                }
+                let expr = parts.into_iter()
+                    .reduce(|left, right| Expression::BinaryOperation(BinaryOperation::Plus, Box::new(left), Box::new(right), pos))
+                    .expect("at least one part");
+                return Ok((after, expr));
+            }
+            Ok((_, '\\')) => match parse_escape(rest) {
+                Ok((after, c)) => {
+                    text.push(c);
+                    rest = after;
+                }
+                Err(nom::Err::Error(_)) => break,
+                Err(err) => return Err(err),
+            },
+            Ok((after, '$')) if after.fragment().starts_with('{') => {
+                let value_pos = position(&after);
+                let (after, value) = delimited(
+                    terminated(char('{'), blanks),
+                    expect(parse_expression, || ParseErrorType::ExprExpected("in `${`".into())),
+                    preceded(blanks, expect(char('}'), || ParseErrorType::MissingToken("}".into(), "to close `${`".into()))),
+                )(after)?;
+                if !text.is_empty() {
+                    parts.push(Expression::Atom(Atom::String(std::mem::take(&mut text)), pos));
+                }
+                parts.push(Expression::MethodCall("str".into(), "tostring".into(), vec![value], value_pos));
+                rest = after;
+            }
+            Ok((after, c)) if c != '\n' && c != '\r' => {
+                text.push(c);
+                rest = after;
+            }
+            _ => break,
+        }
+    }
+    Err(nom::Err::Failure(SyntaxError::new(input, ParseErrorType::UnterminatedString)))
+}
+
+fn parse_boolean(input: Input) -> PResult<Atom> {
     alt((
-        map(tag("true"), |_| Atom::Boolean(true)),
-        map(tag("false"), |_| Atom::Boolean(false)),
+        map(keyword("true"), |_| Atom::Boolean(true)),
+        map(keyword("false"), |_| Atom::Boolean(false)),
     ))(input)
 }
 
-fn parse_double(input: &str) -> IResult<&str, Atom> {
-    map(double, Atom::Double)(input)
+fn parse_na(input: Input) -> PResult<Atom> {
+    map(keyword("na"), |_| Atom::Na)(input)
 }
 
-fn parse_integer(input: &str) -> IResult<&str, Atom> {
-    map(i64, Atom::Integer)(input)
+fn parse_number(input: Input) -> PResult<Atom> {
+    let (rest, text) = recognize(tuple((
+        digit1,
+        opt(pair(char('.'), digit1)),
+        opt(tuple((one_of("eE"), opt(one_of("+-")), digit1))),
+    )))(input)?;
+    let text = *text.fragment();
+
+    let malformed = || nom::Err::Failure(SyntaxError::new(input, ParseErrorType::MalformedNumber(text.to_string())));
+
+    if text.contains(['.', 'e', 'E']) {
+        text.parse().map(|value| (rest, Atom::Double(value))).map_err(|_| malformed())
+    } else {
+        text.parse().map(|value| (rest, Atom::Integer(value))).map_err(|_| malformed())
+    }
 }
 
-fn parse_variable(input: &str) -> IResult<&str, Atom> {
-    map(alpha1, |var: &str| Atom::Variable(var.to_string()))(input)
+fn parse_variable(input: Input) -> PResult<Atom> {
+    let (rest, name) = parse_identifier(input)?;
+    if is_keyword(&name) {
+        return Err(nom::Err::Error(SyntaxError { input, kind: None }));
+    }
+    Ok((rest, Atom::Variable(name)))
 }
 
-fn parse_tuple(input: &str) -> IResult<&str, Expression> {
+fn parse_tuple(input: Input) -> PResult<Expression> {
+    let pos = position(&input);
     let (input, exprs) = delimited(
-        tag("["),
+        terminated(tag("["), whitespace),
         separated_list0(
-            delimited(multispace0, tag(","), multispace0),
+            delimited(whitespace, tag(","), whitespace),
             parse_expression
         ),
-        tag("]")
+        preceded(whitespace, expect(tag("]"), || ParseErrorType::MissingToken("]".into(), "to end the list".into())))
     )(input)?;
-    Ok((input, Expression::Tuple(exprs)))
+    Ok((input, Expression::Tuple(exprs, pos)))
 }
 
-fn parse_atom(input: &str) -> IResult<&str, Expression> {
+fn parse_atom(input: Input) -> PResult<Expression> {
+    let pos = position(&input);
     let parser = alt((
         parse_boolean,
-        parse_double,
-        parse_integer,
+        parse_na,
+        parse_number,
         parse_string,
         parse_variable,
     ));
-    map(parser, |atom| atom.into())(input)
+    map(parser, move |atom| Expression::Atom(atom, pos))(input)
+}
+
+fn parse_parenthesized(input: Input) -> PResult<Expression> {
+    delimited(
+        terminated(char('('), whitespace),
+        expect(parse_expression, || ParseErrorType::ExprExpected("after `(`".into())),
+        preceded(whitespace, expect(char(')'), || ParseErrorType::MissingToken(")".into(), "to close the parentheses".into()))),
+    )(input)
 }
 
-pub fn parse_binary_operator(input: &str) -> IResult<&str, BinaryOperation> {
+/// Parse a keyword operator such as `and`, which must not run into an identifier.
+fn keyword<'a>(word: &'static str) -> impl FnMut(Input<'a>) -> PResult<'a, Input<'a>> {
+    terminated(tag(word), not(satisfy(is_id_continue)))
+}
+
+pub fn parse_binary_operator(input: Input) -> PResult<BinaryOperation> {
     alt((
         map(char('+'), |_| BinaryOperation::Plus),
         map(char('-'), |_| BinaryOperation::Minus),
         map(char('*'), |_| BinaryOperation::Times),
         map(char('/'), |_| BinaryOperation::Divide),
+        map(char('%'), |_| BinaryOperation::Modulo),
         map(tag("=="), |_| BinaryOperation::Equal),
         map(tag("!="), |_| BinaryOperation::NotEqual),
+        map(tag("<="), |_| BinaryOperation::LessThanEqual),
+        map(tag(">="), |_| BinaryOperation::GreaterThanEqual),
+        map(char('<'), |_| BinaryOperation::LessThan),
+        map(char('>'), |_| BinaryOperation::GreaterThan),
+        map(keyword("and"), |_| BinaryOperation::And),
+        map(keyword("or"), |_| BinaryOperation::Or),
     ))(input)
 }
 
-pub fn parse_binary_operation(input: &str) -> IResult<&str, Expression> {
-    let (rest, (left, _, op, _, right)) = tuple((
-        parse_atom,
-        multispace0,
-        parse_binary_operator,
-        multispace0,
+fn parse_unary_operator(input: Input) -> PResult<UnaryOperation> {
+    alt((
+        map(char('-'), |_| UnaryOperation::Negate),
+        map(char('!'), |_| UnaryOperation::Not),
+        map(keyword("not"), |_| UnaryOperation::Not),
+    ))(input)
+}
+
+fn parse_unary_operation(input: Input) -> PResult<Expression> {
+    let pos = position(&input);
+    let (input, op) = parse_unary_operator(input)?;
+    let (input, _) = blanks(input)?;
+    let (input, operand) = expect(parse_operand, || ParseErrorType::ExprExpected(format!("after `{}`", op)))(input)?;
+    Ok((input, Expression::UnaryOperation(op, Box::new(operand), pos)))
+}
+
+fn parse_primary(input: Input) -> PResult<Expression> {
+    alt((
+        parse_parenthesized,
+        parse_tuple,
+        parse_import,
+        parse_method_call,
+        parse_function_call,
+        parse_interpolated_string,
         parse_atom,
-    ))(input)?;
+    ))(input)
+}
 
-    let expression = Expression::BinaryOperation(op, Box::new(left), Box::new(right));
-    Ok((rest, expression))
+/// Parse a history reference `[offset]` directly following its series.
+fn parse_history_offset(input: Input) -> PResult<(Expression, Position)> {
+    let pos = position(&input);
+    let (input, offset) = preceded(
+        terminated(tag("["), blanks),
+        expect(parse_expression, || ParseErrorType::ExprExpected("as history offset".into())),
+    )(input)?;
+    let (input, _) = preceded(blanks, expect(tag("]"), || ParseErrorType::MissingToken("]".into(), "to close the history offset".into())))(input)?;
+    Ok((input, (offset, pos)))
 }
 
-fn parse_function_call(input: &str) -> IResult<&str, Expression> {
-    let (input, func_name) = parse_identifier(input)?;
-    let (input, args) = delimited(
-        tag("("),
+fn parse_postfix(input: Input) -> PResult<Expression> {
+    let (input, expr) = parse_primary(input)?;
+    let (input, offsets) = many0(parse_history_offset)(input)?;
+    let expr = offsets.into_iter().fold(expr, |expr, (offset, pos)| {
+        Expression::History(Box::new(expr), Box::new(offset), pos)
+    });
+    Ok((input, expr))
+}
+
+/// Parse a single operand of a binary expression, including any prefix and postfix operators.
+fn parse_operand(input: Input) -> PResult<Expression> {
+    alt((
+        parse_unary_operation,
+        parse_postfix,
+    ))(input)
+}
+
+/// Precedence climbing: keep folding operators that bind at least as tightly as
+/// `min_precedence` into the left-hand side, recursing for tighter right-hand sides.
+fn parse_binary_expression(input: Input, min_precedence: u8) -> PResult<Expression> {
+    let (mut input, mut left) = parse_operand(input)?;
+
+    loop {
+        let (rest, _) = blanks(input)?;
+        let pos = position(&rest);
+        let (rest, op) = match parse_binary_operator(rest) {
+            Ok(result) => result,
+            Err(nom::Err::Error(_)) => break,
+            Err(err) => return Err(err),
+        };
+
+        let precedence = op.precedence();
+        if precedence < min_precedence {
+            break;
+        }
+
+        let (rest, _) = blanks(rest)?;
+        let (rest, right) = expect(
+            |i| parse_binary_expression(i, precedence + 1),
+            || ParseErrorType::ExprExpected(format!("after `{}`", op)),
+        )(rest)?;
+        left = Expression::BinaryOperation(op, Box::new(left), Box::new(right), pos);
+        input = rest;
+    }
+
+    Ok((input, left))
+}
+
+pub fn parse_binary_operation(input: Input) -> PResult<Expression> {
+    parse_binary_expression(input, 0)
+}
+
+fn parse_arguments(input: Input) -> PResult<Vec<Expression>> {
+    delimited(
+        terminated(tag("("), whitespace),
         separated_list0(
-            delimited(multispace0, tag(","), multispace0),
+            delimited(whitespace, tag(","), whitespace),
             parse_expression
         ),
-        tag(")")
+        preceded(whitespace, expect(tag(")"), || ParseErrorType::MissingToken(")".into(), "after arguments".into())))
+    )(input)
+}
+
+/// Positional and named arguments of a call.
+type CallArguments = (Vec<Expression>, Vec<(String, Expression)>);
+
+/// Arguments of a call to a script function: positional ones and named `name = value` ones.
+fn parse_call_arguments(input: Input) -> PResult<CallArguments> {
+    let named_argument = pair(
+        terminated(parse_identifier, tuple((blanks, tag("="), not(one_of("=>")), blanks))),
+        parse_expression,
+    );
+    let mut argument = alt((
+        map(named_argument, |(name, expr)| (Some(name), expr)),
+        map(parse_expression, |expr| (None, expr)),
+    ));
+    let (input, args) = delimited(
+        terminated(tag("("), whitespace),
+        separated_list0(
+            delimited(whitespace, tag(","), whitespace),
+            // Keep where each argument starts to report misplaced positional arguments
+            move |input| argument(input).map(|(rest, arg)| (rest, (input, arg))),
+        ),
+        preceded(whitespace, expect(tag(")"), || ParseErrorType::MissingToken(")".into(), "after arguments".into())))
     )(input)?;
-    Ok((input, Expression::FunctionCall(func_name, args)))
+
+    let mut positional = Vec::new();
+    let mut named = Vec::new();
+    for (start, (name, expr)) in args {
+        match name {
+            Some(name) => named.push((name, expr)),
+            None if !named.is_empty() => {
+                return Err(nom::Err::Failure(SyntaxError::new(start, ParseErrorType::PositionalAfterNamed)));
+            }
+            None => positional.push(expr),
+        }
+    }
+    Ok((input, (positional, named)))
+}
+
+fn parse_function_call(input: Input) -> PResult<Expression> {
+    let pos = position(&input);
+    let (input, func_name) = parse_identifier(input)?;
+    let (input, (args, named)) = parse_call_arguments(input)?;
+    Ok((input, Expression::FunctionCall(func_name, args, named, pos)))
 }
 
 
-fn parse_import(input: &str) -> IResult<&str, Expression> {
-    let (input, _) = tag("import")(input)?;
-    let (input, _) = space0(input)?;
+fn parse_import(input: Input) -> PResult<Expression> {
+    let pos = position(&input);
+    let (input, _) = keyword("import")(input)?;
+    let (input, _) = blanks(input)?;
     let (input, library_name) = parse_identifier(input)?;
-    Ok((input, Expression::MethodCall("import".to_string(), library_name, Vec::new())))
+    Ok((input, Expression::MethodCall("import".to_string(), library_name, Vec::new(), pos)))
 }
 
-fn parse_method_call(input: &str) -> IResult<&str, Expression> {
+fn parse_method_call(input: Input) -> PResult<Expression> {
+    let pos = position(&input);
     let (input, obj_name) = parse_identifier(input)?;
-    let (input, _) = space0(input)?;
-    let (input, method_name) = delimited(tag("."), parse_identifier, space0)(input)?;
-    let (input, args) = delimited(
-        tag("("),
-        separated_list0(
-            delimited(space0, tag(","), space0),
-            parse_expression
-        ),
-        tag(")")
-    )(input)?;
-    Ok((input, Expression::MethodCall(obj_name, method_name, args)))
+    let (input, _) = blanks(input)?;
+    let (input, method_name) = preceded(tag("."), parse_identifier)(input)?;
+    // Without arguments, `lib.name` reads a library constant such as `math.pi`
+    let (input, args) = opt(preceded(blanks, parse_arguments))(input)?;
+    let args = args.unwrap_or_default();
+    Ok((input, Expression::MethodCall(obj_name, method_name, args, pos)))
 }
 
-pub(crate) fn parse_expression(input: &str) -> IResult<&str, Expression> {
+pub(crate) fn parse_expression(input: Input) -> PResult<Expression> {
     alt((
         map(parse_function, Expression::from),
-        parse_binary_operation,
-        parse_tuple,
-        parse_import,
-        parse_method_call,
-        parse_function_call, // Add function call parsing here
-        parse_atom,
+        map(parse_lambda, Expression::from),
+        parse_if,
+        parse_for,
+        parse_while,
+        parse_ternary,
     ))(input)
-}
\ No newline at end of file
+}
+
+/// Parse `condition ? then : else`, binding looser than any binary operator.
+fn parse_ternary(input: Input) -> PResult<Expression> {
+    let (input, condition) = parse_binary_operation(input)?;
+    let (rest, _) = blanks(input)?;
+    let pos = position(&rest);
+    let (rest, question) = opt(char('?'))(rest)?;
+    if question.is_none() {
+        return Ok((input, condition));
+    }
+    let (rest, then_expr) = preceded(
+        blanks,
+        expect(parse_expression, || ParseErrorType::ExprExpected("after `?`".into())),
+    )(rest)?;
+    let (rest, _) = preceded(blanks, expect(char(':'), || ParseErrorType::MissingToken(":".into(), "in conditional expression".into())))(rest)?;
+    let (rest, else_expr) = preceded(
+        blanks,
+        expect(parse_expression, || ParseErrorType::ExprExpected("after `:`".into())),
+    )(rest)?;
+    Ok((rest, Expression::Ternary(Box::new(condition), Box::new(then_expr), Box::new(else_expr), pos)))
+}
@@ -1,63 +1,398 @@
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-//     use super::nom::combinator::all_consuming;
-//
-//     #[test]
-//     fn test_parse_identifier() {
-//         let result = parse_identifier("foo");
-//         assert_eq!(result, Ok(("", "foo".to_string())));
-//     }
-//
-//     #[test]
-//     fn test_parse_atom() {
-//         let result = parse_atom("123");
-//         assert_eq!(result, Ok(("", Expression::Atom(Atom::Integer(123)))));
-//     }
-//
-//     #[test]
-//     fn test_parse_expression() {
-//         let result = parse_expression("foo + 1");
-//         assert!(result.is_ok());
-//     }
-//
-//     #[test]
-//     fn test_context_variable() {
-//         let context = Context::new();
-//         context.set_variable("x".to_string(), Expression::Atom(Atom::Integer(42)));
-//         let value = context.get_variable("x").unwrap();
-//         assert_eq!(value, Expression::Atom(Atom::Integer(42)));
-//     }
-//
-//     #[test]
-//     fn test_expression_evaluation() {
-//         let context = Context::new();
-//         context.set_variable("x".to_string(), Expression::Atom(Atom::Integer(42)));
-//         let expr = Expression::Atom(Atom::Variable("x".to_string()));
-//         let value = expr.evaluate(&context).unwrap();
-//         assert_eq!(value, Expression::Atom(Atom::Integer(42)));
-//     }
-//
-//     #[test]
-//     fn test_function_call() {
-//         let context = Context::new();
-//         let func = Function {
-//             name: "add".to_string(),
-//             parameters: vec![
-//                 Parameter { name: "a".to_string(), default_value: None },
-//                 Parameter { name: "b".to_string(), default_value: None },
-//             ],
-//             body: Block {
-//                 statements: vec![],
-//                 return_expr: Expression::BinaryOperation(
-//                     BinaryOperation::Plus,
-//                     Box::new(Expression::Atom(Atom::Variable("a".to_string()))),
-//                     Box::new(Expression::Atom(Atom::Variable("b".to_string()))),
-//                 ),
-//             },
-//         };
-//         context.set_function("add".to_string(), func);
-//         let result = context.run_ast("x = add(1, 2)").unwrap();
-//         assert_eq!(result.get("x").unwrap(), &Expression::Atom(Atom::Integer(3)));
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use crate::ast::atom::Atom;
+    use crate::ast::binaryop::BinaryOperation;
+    use crate::ast::expression::Expression;
+    use crate::ast::stmt::Statement;
+    use crate::ast::unaryop::UnaryOperation;
+    use crate::parser::parser::{parse_ast, parse_expression, parse_script, parse_statement, Input};
+    use crate::types::{ParseErrorType, Position};
+
+    fn parse(code: &str) -> Expression {
+        let (rest, expr) = parse_expression(Input::new(code)).unwrap();
+        assert_eq!(*rest.fragment(), "", "unparsed input");
+        expr
+    }
+
+    fn is_var(expr: &Expression, name: &str) -> bool {
+        matches!(expr, Expression::Atom(Atom::Variable(var), _) if var == name)
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        match parse("a + b * c") {
+            Expression::BinaryOperation(BinaryOperation::Plus, left, right, _) => {
+                assert!(is_var(&left, "a"));
+                assert!(matches!(*right, Expression::BinaryOperation(BinaryOperation::Times, ..)));
+            }
+            expr => panic!("unexpected {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_parse_left_associative() {
+        match parse("a - b - c") {
+            Expression::BinaryOperation(BinaryOperation::Minus, left, right, _) => {
+                assert!(matches!(*left, Expression::BinaryOperation(BinaryOperation::Minus, ..)));
+                assert!(is_var(&right, "c"));
+            }
+            expr => panic!("unexpected {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_parse_parentheses_and_unary() {
+        match parse("-(x + y) / 2") {
+            Expression::BinaryOperation(BinaryOperation::Divide, left, right, _) => {
+                assert!(matches!(
+                    *left,
+                    Expression::UnaryOperation(UnaryOperation::Negate, ref operand, _)
+                        if matches!(**operand, Expression::BinaryOperation(BinaryOperation::Plus, ..))
+                ));
+                assert!(matches!(*right, Expression::Atom(Atom::Integer(2), _)));
+            }
+            expr => panic!("unexpected {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_parse_logical_operators() {
+        match parse("not a or b and c >= 1") {
+            Expression::BinaryOperation(BinaryOperation::Or, left, right, _) => {
+                assert!(matches!(*left, Expression::UnaryOperation(UnaryOperation::Not, ..)));
+                assert!(matches!(
+                    *right,
+                    Expression::BinaryOperation(BinaryOperation::And, _, ref cmp, _)
+                        if matches!(**cmp, Expression::BinaryOperation(BinaryOperation::GreaterThanEqual, ..))
+                ));
+            }
+            expr => panic!("unexpected {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_parse_call_operands() {
+        match parse("math.abs(x) * f(1, 2) + [a, b]") {
+            Expression::BinaryOperation(BinaryOperation::Plus, left, right, _) => {
+                assert!(matches!(*left, Expression::BinaryOperation(BinaryOperation::Times, ..)));
+                assert!(matches!(*right, Expression::Tuple(..)));
+            }
+            expr => panic!("unexpected {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_parse_stops_at_newline() {
+        let (rest, expr) = parse_expression(Input::new("a + 1\nb = 2")).unwrap();
+        assert_eq!(*rest.fragment(), "\nb = 2");
+        assert!(matches!(expr, Expression::BinaryOperation(..)));
+    }
+
+    #[test]
+    fn test_parse_history_reference() {
+        // `-x[1] * 2` is `(-(x[1])) * 2`
+        match parse("-x[1] * 2") {
+            Expression::BinaryOperation(BinaryOperation::Times, left, _, _) => match *left {
+                Expression::UnaryOperation(UnaryOperation::Negate, operand, _) => {
+                    assert!(matches!(*operand, Expression::History(_, _, pos) if pos == Position::new(1, 3)));
+                }
+                expr => panic!("unexpected {:?}", expr),
+            },
+            expr => panic!("unexpected {:?}", expr),
+        }
+        assert!(matches!(parse("ta.sma(close, 3)[n + 1]"), Expression::History(..)));
+        assert!(parse_expression(Input::new("x[1")).is_err());
+    }
+
+    #[test]
+    fn test_parse_if_else() {
+        let code = "y = if a\n    b = 1\n    b\nelse if c\n    2\nelse\n    x = 3\nz = 4\n";
+        let statements = parse_script(code).unwrap();
+        assert_eq!(statements.len(), 2);
+
+        match &statements[0] {
+            Statement::VariableDeclaration(_, Expression::If(_, then_block, Some(else_block), pos), _) => {
+                assert_eq!(*pos, Position::new(1, 5));
+                assert_eq!(then_block.statements.len(), 1);
+                assert!(then_block.return_expr.is_some());
+                match &else_block.return_expr {
+                    Some(Expression::If(_, _, Some(last), _)) => assert!(last.return_expr.is_none()),
+                    expr => panic!("unexpected {:?}", expr),
+                }
+            }
+            statement => panic!("unexpected {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn test_parse_function_block() {
+        let code = "fun(x, y) =>\n    a = x + y\n\n    b = x - y\n    [a, b]\n[m, n] = fun(8, 5)\n";
+        let statements = parse_script(code).unwrap();
+        assert_eq!(statements.len(), 2);
+
+        match &statements[0] {
+            Statement::FunctionDefinition(func) => {
+                assert_eq!(func.body.statements.len(), 2);
+                assert!(matches!(func.body.return_expr, Some(Expression::Tuple(..))));
+            }
+            statement => panic!("unexpected {:?}", statement),
+        }
+        assert!(matches!(statements[1], Statement::TupleAssignment(..)));
+    }
+
+    #[test]
+    fn test_parse_indentation_errors() {
+        let errors = parse_script("f(x) =>\n    a = x\n      b = 2\n").unwrap_err();
+        assert_eq!(errors[0].to_string(), "unexpected indentation (line 3, position 7)");
+
+        let errors = parse_script("f(x) =>\n    a = x\n  a\n").unwrap_err();
+        assert_eq!(errors[0].to_string(), "indentation does not match any outer block (line 3, position 3)");
+
+        let errors = parse_script("f(x) =>\n\ta = x\n    a\n").unwrap_err();
+        assert_eq!(errors[0].err_type(), &ParseErrorType::InconsistentIndent);
+
+        let errors = parse_script("x = 1\n  y = 2\nz = 3\n").unwrap_err();
+        assert_eq!(errors, vec![ParseErrorType::UnexpectedIndent.into_err(Position::new(2, 3))]);
+    }
+
+    #[test]
+    fn test_parse_comments() {
+        let code = "// header\n/* block\n   comment */\nx = 1 // trailing\ny = x /* inline */ + 2\nif x == 1\n    // inside\n    z = 3\n  // dedented comment\n    w = f(1, // first\n          2)\n// end";
+        let statements = parse_script(code).unwrap();
+        assert_eq!(statements.len(), 3);
+        match &statements[2] {
+            Statement::Expression(Expression::If(_, then_block, None, _)) => {
+                assert_eq!(then_block.statements.len(), 2);
+            }
+            statement => panic!("unexpected {:?}", statement),
+        }
+
+        // A comment starting a line is not indentation
+        let statements = parse_script("x = 1\n/* note */ y = 2\n").unwrap();
+        assert_eq!(statements.len(), 2);
+
+        let errors = parse_script("x = 1\ny = 2 /* open\n").unwrap_err();
+        assert_eq!(errors[0].to_string(), "unterminated block comment (line 2, position 7)");
+    }
+
+    #[test]
+    fn test_parse_annotations() {
+        let ast = parse_ast("//@version=5\n//@description My script\n// not an annotation\nx = 1 //@ignored=1\n").unwrap();
+        assert_eq!(ast.statements.len(), 1);
+        assert_eq!(ast.annotations.len(), 2);
+        assert_eq!(ast.annotations[1].pos, Position::new(2, 1));
+        assert_eq!(ast.version(), Some(5));
+        assert_eq!(ast.annotation("description"), Some("My script"));
+        assert_eq!(ast.annotation("ignored"), None);
+    }
+
+    #[test]
+    fn test_parse_identifiers() {
+        let statements = parse_script("sma20 = 1\nfast_len = sma20\n_x = trueish\n").unwrap();
+        assert_eq!(statements.len(), 3);
+        assert!(matches!(&statements[1], Statement::VariableDeclaration(name, expr, _) if name == "fast_len" && is_var(expr, "sma20")));
+        assert!(matches!(&statements[2], Statement::VariableDeclaration(_, expr, _) if is_var(expr, "trueish")));
+
+        let errors = parse_script("true = 1\nf(if) => 1\nfor na = 1 to 2\n    1\n[a, or] = [1, 2]\nx = import\n").unwrap_err();
+        assert_eq!(errors[0].to_string(), "`true` is a reserved keyword and cannot be used as a name (line 1, position 1)");
+        assert_eq!(*errors[1].err_type(), ParseErrorType::Reserved("if".into()));
+        assert_eq!(errors[1].position(), Position::new(2, 3));
+        assert_eq!(*errors[2].err_type(), ParseErrorType::Reserved("na".into()));
+        assert_eq!(errors[3].position(), Position::new(5, 5));
+        assert_eq!(*errors[4].err_type(), ParseErrorType::ExprExpected("after `=`".into()));
+    }
+
+    #[cfg(feature = "unicode-xid-ident")]
+    #[test]
+    fn test_parse_unicode_identifiers() {
+        let statements = parse_script("цена = 2\nprix_été = цена\n").unwrap();
+        assert!(matches!(&statements[1], Statement::VariableDeclaration(name, expr, _) if name == "prix_été" && is_var(expr, "цена")));
+    }
+
+    #[test]
+    fn test_parse_named_arguments() {
+        match parse("f(close, a == b, len = 14)") {
+            Expression::FunctionCall(name, args, named, _) => {
+                assert_eq!(name, "f");
+                assert_eq!(args.len(), 2);
+                assert!(matches!(args[1], Expression::BinaryOperation(BinaryOperation::Equal, ..)));
+                assert_eq!(named.len(), 1);
+                assert_eq!(named[0].0, "len");
+                assert!(matches!(named[0].1, Expression::Atom(Atom::Integer(14), _)));
+            }
+            expr => panic!("unexpected {:?}", expr),
+        }
+
+        let errors = parse_script("f(a = 1, 5)\n").unwrap_err();
+        assert_eq!(errors, vec![ParseErrorType::PositionalAfterNamed.into_err(Position::new(1, 10))]);
+    }
+
+    #[test]
+    fn test_parse_lambda() {
+        match parse("(x, y = 2) => x * y") {
+            Expression::Function(func) => {
+                assert!(func.name.is_empty());
+                assert_eq!(func.parameters.len(), 2);
+                assert!(func.parameters[1].default_value.is_some());
+                assert!(matches!(func.body.return_expr, Some(Expression::BinaryOperation(BinaryOperation::Times, ..))));
+            }
+            expr => panic!("unexpected {:?}", expr),
+        }
+        // A parenthesized expression is not a lambda
+        assert!(matches!(parse("(x) * 2"), Expression::BinaryOperation(BinaryOperation::Times, ..)));
+
+        let errors = parse_script("f = (x) =>\ng = (in) => 1\n").unwrap_err();
+        assert_eq!(errors[0].to_string(), "expected a body for function `<anonymous>` (line 1, position 11)");
+        assert_eq!(*errors[1].err_type(), ParseErrorType::Reserved("in".into()));
+    }
+
+    #[test]
+    fn test_parse_loops() {
+        let code = "for i = 0 to n - 1 by 2\n    break\nfor x in xs\n    continue\nwhile a < b\n    a = a + 1\n";
+        let statements = parse_script(code).unwrap();
+        assert_eq!(statements.len(), 3);
+
+        match &statements[0] {
+            Statement::Expression(Expression::For(var, _, to, Some(_), body, _)) => {
+                assert_eq!(var, "i");
+                assert!(matches!(**to, Expression::BinaryOperation(BinaryOperation::Minus, ..)));
+                assert_eq!(body.statements, vec![Statement::Break(Position::new(2, 5))]);
+            }
+            statement => panic!("unexpected {:?}", statement),
+        }
+        assert!(matches!(statements[1], Statement::Expression(Expression::ForIn(..))));
+        assert!(matches!(statements[2], Statement::Expression(Expression::While(..))));
+
+        let errors = parse_script("for i 0 to 1\n    i\nfor i = 0 1\n    i\n").unwrap_err();
+        assert_eq!(errors[0].to_string(), "expected `=` or `in` after the loop variable (line 1, position 7)");
+        assert_eq!(errors[1].to_string(), "expected `to` after the initial value (line 3, position 11)");
+    }
+
+    #[test]
+    fn test_parse_assignments() {
+        let statements = parse_script("x = 1\nx := 2\nx -= y * 2\n").unwrap();
+        assert!(matches!(&statements[0], Statement::VariableDeclaration(name, ..) if name == "x"));
+        assert!(matches!(&statements[1], Statement::Assignment(name, Expression::Atom(Atom::Integer(2), _), _) if name == "x"));
+        match &statements[2] {
+            Statement::Assignment(name, Expression::BinaryOperation(BinaryOperation::Minus, left, right, pos), _) => {
+                assert_eq!(name, "x");
+                assert!(is_var(left, "x"));
+                assert!(matches!(**right, Expression::BinaryOperation(BinaryOperation::Times, ..)));
+                assert_eq!(*pos, Position::new(3, 3));
+            }
+            statement => panic!("unexpected {:?}", statement),
+        }
+
+        let errors = parse_script("x :=\ny *= \n").unwrap_err();
+        assert_eq!(errors[0].to_string(), "expected an expression after `:=` (line 1, position 5)");
+        assert_eq!(errors[1].to_string(), "expected an expression after `*=` (line 2, position 6)");
+    }
+
+    #[test]
+    fn test_parse_persistent_declarations() {
+        let statements = parse_script("var count = 0\nvarip last = close\nvariance = 1\n").unwrap();
+        assert!(matches!(&statements[0], Statement::PersistentDeclaration(name, _, pos) if name == "count" && *pos == Position::new(1, 1)));
+        assert!(matches!(&statements[1], Statement::PersistentDeclaration(name, expr, _) if name == "last" && is_var(expr, "close")));
+        assert!(matches!(&statements[2], Statement::VariableDeclaration(name, ..) if name == "variance"));
+
+        let errors = parse_script("var = 1\nvar x 1\n").unwrap_err();
+        assert_eq!(errors[0].to_string(), "expected `variable` after `var` (line 1, position 5)");
+        assert_eq!(errors[1].to_string(), "expected `=` after the variable name (line 2, position 7)");
+    }
+
+    #[test]
+    fn test_parse_ternary() {
+        // `?:` binds looser than `or` and nests to the right
+        match parse("a or b ? 1 : c ? 2 : 3") {
+            Expression::Ternary(condition, _, else_expr, _) => {
+                assert!(matches!(*condition, Expression::BinaryOperation(BinaryOperation::Or, ..)));
+                assert!(matches!(*else_expr, Expression::Ternary(..)));
+            }
+            expr => panic!("unexpected {:?}", expr),
+        }
+
+        let errors = parse_script("x = a ? 1\nif a\ny = 1\n").unwrap_err();
+        assert_eq!(errors[0].to_string(), "expected `:` in conditional expression (line 1, position 10)");
+        assert_eq!(errors[1].to_string(), "expected an indented block after `if` condition (line 2, position 5)");
+    }
+
+    #[test]
+    fn test_parse_positions() {
+        let (_, stmt) = parse_statement(Input::new("x = a +\tfoo(1)")).unwrap();
+        assert_eq!(stmt.position(), Position::new(1, 1));
+
+        match stmt {
+            Statement::VariableDeclaration(_, Expression::BinaryOperation(_, left, right, pos), _) => {
+                assert_eq!(pos, Position::new(1, 7));
+                assert_eq!(left.position(), Position::new(1, 5));
+                assert_eq!(right.position(), Position::new(1, 9));
+            }
+            stmt => panic!("unexpected {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let statements = parse_script("x = 1\n\ny = x * 2\n").unwrap();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[1].position(), Position::new(3, 1));
+    }
+
+    #[test]
+    fn test_parse_strings() {
+        let string = |code: &str| match parse(code) {
+            Expression::Atom(Atom::String(s), _) => s,
+            expr => panic!("unexpected {:?}", expr),
+        };
+        assert_eq!(string(r#""say \"hi\"\n\tok""#), "say \"hi\"\n\tok");
+        assert_eq!(string(r#"'it\'s "quoted"'"#), "it's \"quoted\"");
+        assert_eq!(string(r#""\\ \u{20AC}\0""#), "\\ \u{20AC}\0");
+
+        let errors = parse_script("x = \"bad \\q\"\ny = 'open\nz = \"\\u{110000}\"\n").unwrap_err();
+        assert_eq!(errors[0].to_string(), "invalid escape sequence `\\q` (line 1, position 10)");
+        assert_eq!(errors[1].to_string(), "unterminated string literal (line 2, position 5)");
+        assert_eq!(*errors[2].err_type(), ParseErrorType::MalformedEscapeSequence("\\u{110000}".into()));
+    }
+
+    #[test]
+    fn test_parse_interpolated_strings() {
+        assert!(matches!(parse("`plain`"), Expression::Atom(Atom::String(s), _) if s == "plain"));
+        match parse("`a ${x + 1} b`") {
+            Expression::BinaryOperation(BinaryOperation::Plus, left, right, _) => {
+                assert!(matches!(*right, Expression::Atom(Atom::String(ref s), _) if s == " b"));
+                assert!(matches!(*left, Expression::BinaryOperation(BinaryOperation::Plus, ..)));
+            }
+            expr => panic!("unexpected {:?}", expr),
+        }
+
+        let errors = parse_script("x = `a ${}`\ny = `b ${1\nz = `open\n").unwrap_err();
+        assert_eq!(errors[0].to_string(), "expected an expression in `${` (line 1, position 10)");
+        assert_eq!(errors[1].to_string(), "expected `}` to close `${` (line 2, position 11)");
+        assert_eq!(errors[2].to_string(), "unterminated string literal (line 3, position 5)");
+    }
+
+    #[test]
+    fn test_parse_error_messages() {
+        let errors = parse_script("x = foo(1, 2").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "expected `)` after arguments (line 1, position 13)");
+
+        let errors = parse_script("x = 1 +").unwrap_err();
+        assert_eq!(*errors[0].err_type(), ParseErrorType::ExprExpected("after `+`".into()));
+    }
+
+    #[test]
+    fn test_parse_error_trailing_input() {
+        let errors = parse_script("x = 1 2").unwrap_err();
+        assert_eq!(*errors[0].err_type(), ParseErrorType::UnexpectedInput("2".into()));
+        assert_eq!(errors[0].position(), Position::new(1, 7));
+    }
+
+    #[test]
+    fn test_parse_error_recovery() {
+        let errors = parse_script("x = (1\ny = 2\nz = \"abc\nw = 3 +\n").unwrap_err();
+        let lines: Vec<_> = errors.iter().map(|err| err.position().line()).collect();
+        assert_eq!(lines, vec![Some(2), Some(3), Some(4)]);
+        assert_eq!(*errors[1].err_type(), ParseErrorType::UnterminatedString);
+    }
+}
@@ -7,14 +7,15 @@ use nom::branch::alt;
 use nom::combinator::map;
 use crate::ast::atom::Atom;
 use crate::ast::expression::Expression;
+use crate::types::Position;
 
 fn parse_expression(input: &str) -> IResult<&str, Expression> {
     alt((
         map(digit1, |s: &str| {
-            Expression::Atom(Atom::Integer(s.parse().unwrap()))
+            Atom::Integer(s.parse().unwrap()).into()
         }),
         map(preceded(space0, delimited(char('"'), alpha1, char('"'))), |s: &str| {
-            Expression::Atom(Atom::String(s.to_string()))
+            Atom::String(s.to_string()).into()
         }),
     ))(input)
 }
@@ -30,7 +31,7 @@ fn parse_method_call(input: &str) -> IResult<&str, Expression> {
         char(')'),
     ))(input)?;
 
-    Ok((input, Expression::MethodCall(obj_name.to_string(), method_name.to_string(), args)))
+    Ok((input, Expression::MethodCall(obj_name.to_string(), method_name.to_string(), args, Position::NONE)))
 }
 
 #[cfg(test)]
@@ -0,0 +1,41 @@
+use crate::FLOAT;
+
+/// One bar of OHLCV market data.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Bar {
+    /// Opening time of the bar, in milliseconds since the Unix epoch.
+    pub time: i64,
+    pub open: FLOAT,
+    pub high: FLOAT,
+    pub low: FLOAT,
+    pub close: FLOAT,
+    pub volume: FLOAT,
+}
+
+impl Bar {
+    /// Create a new [`Bar`].
+    #[inline]
+    #[must_use]
+    pub const fn new(time: i64, open: FLOAT, high: FLOAT, low: FLOAT, close: FLOAT, volume: FLOAT) -> Self {
+        Self { time, open, high, low, close, volume }
+    }
+}
+
+/// Bars shared by the tests that run scripts over a feed.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::Bar;
+    use crate::FLOAT;
+
+    /// Bars numbered from 0 with the given `(open, high, low, close, volume)`.
+    pub(crate) fn bars(values: impl IntoIterator<Item = (FLOAT, FLOAT, FLOAT, FLOAT, FLOAT)>) -> Vec<Bar> {
+        values.into_iter().enumerate()
+            .map(|(i, (open, high, low, close, volume))| Bar::new(i as i64, open, high, low, close, volume))
+            .collect()
+    }
+
+    /// Bars with all four prices at each of `closes` and a volume of 1.
+    pub(crate) fn closes(closes: &[FLOAT]) -> Vec<Bar> {
+        bars(closes.iter().map(|&c| (c, c, c, c, 1.0)))
+    }
+}
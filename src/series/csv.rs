@@ -0,0 +1,358 @@
+use std::fs;
+use std::path::Path;
+use crate::series::{Bar, DataFeed};
+use crate::{RhaiResultOf, ERR, FLOAT};
+
+/// A field of a [`Bar`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    Time,
+    Open,
+    High,
+    Low,
+    Close,
+    Volume,
+}
+
+impl Field {
+    const ALL: [Field; 6] = [Field::Time, Field::Open, Field::High, Field::Low, Field::Close, Field::Volume];
+
+    /// Default column name of the field.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Field::Time => "time",
+            Field::Open => "open",
+            Field::High => "high",
+            Field::Low => "low",
+            Field::Close => "close",
+            Field::Volume => "volume",
+        }
+    }
+}
+
+/// A CSV column, by header name or by zero-based index.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Column {
+    Name(String),
+    Index(usize),
+}
+
+impl From<&str> for Column {
+    #[inline]
+    fn from(name: &str) -> Self {
+        Column::Name(name.into())
+    }
+}
+
+impl From<String> for Column {
+    #[inline]
+    fn from(name: String) -> Self {
+        Column::Name(name)
+    }
+}
+
+impl From<usize> for Column {
+    #[inline]
+    fn from(index: usize) -> Self {
+        Column::Index(index)
+    }
+}
+
+/// How timestamps are written in the time column.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TimeFormat {
+    /// Seconds since the Unix epoch.
+    UnixSeconds,
+    /// Milliseconds since the Unix epoch.
+    UnixMillis,
+    /// A UTC date/time pattern made of `%Y`, `%m`, `%d`, `%H`, `%M`, `%S` and literal characters,
+    /// e.g. `%Y-%m-%d %H:%M:%S`.
+    Pattern(String),
+}
+
+impl TimeFormat {
+    /// Parse a timestamp into milliseconds since the Unix epoch.
+    pub fn parse(&self, text: &str) -> Result<i64, String> {
+        match self {
+            TimeFormat::UnixSeconds => text.parse::<i64>().map(|s| s * 1000).map_err(|err| err.to_string()),
+            TimeFormat::UnixMillis => text.parse::<i64>().map_err(|err| err.to_string()),
+            TimeFormat::Pattern(pattern) => parse_time_pattern(pattern, text),
+        }
+    }
+}
+
+fn parse_time_pattern(pattern: &str, text: &str) -> Result<i64, String> {
+    let mismatch = || format!("'{}' does not match time format '{}'", text, pattern);
+    let (mut year, mut month, mut day, mut hour, mut minute, mut second) = (1970, 1, 1, 0, 0, 0);
+    let mut rest = text;
+    let mut chars = pattern.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            rest = rest.strip_prefix(c).ok_or_else(mismatch)?;
+            continue;
+        }
+        let (field, width) = match chars.next() {
+            Some('Y') => (&mut year, 4),
+            Some('m') => (&mut month, 2),
+            Some('d') => (&mut day, 2),
+            Some('H') => (&mut hour, 2),
+            Some('M') => (&mut minute, 2),
+            Some('S') => (&mut second, 2),
+            Some(c) => return Err(format!("unsupported time format specifier '%{}'", c)),
+            None => return Err("time format ends with '%'".into()),
+        };
+        let digits = rest.bytes().take(width).take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return Err(mismatch());
+        }
+        *field = rest[..digits].parse().map_err(|_| mismatch())?;
+        rest = &rest[digits..];
+    }
+
+    if !rest.is_empty() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(mismatch());
+    }
+
+    let days = days_from_civil(year, month, day);
+    Ok(((days * 24 + hour) * 60 + minute) * 60_000 + second * 1000)
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Options for reading a [`CsvFeed`].
+///
+/// By default fields are comma-separated, the first line is a header, columns are named
+/// `time`, `open`, `high`, `low`, `close` and `volume` (case-insensitive) and times are
+/// Unix milliseconds. A `volume` column is optional.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    delimiter: char,
+    has_header: bool,
+    columns: [Column; 6],
+    time_format: TimeFormat,
+}
+
+impl Default for CsvOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CsvOptions {
+    /// Create the default [`CsvOptions`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            delimiter: ',',
+            has_header: true,
+            columns: Field::ALL.map(|field| Column::Name(field.name().into())),
+            time_format: TimeFormat::UnixMillis,
+        }
+    }
+
+    /// Set the field delimiter.
+    #[inline]
+    #[must_use]
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Set whether the first line is a header. Without a header, columns must be given by index.
+    #[inline]
+    #[must_use]
+    pub fn with_header(mut self, has_header: bool) -> Self {
+        self.has_header = has_header;
+        self
+    }
+
+    /// Read `field` from `column`.
+    #[inline]
+    #[must_use]
+    pub fn with_column(mut self, field: Field, column: impl Into<Column>) -> Self {
+        self.columns[field as usize] = column.into();
+        self
+    }
+
+    /// Set how timestamps are written.
+    #[inline]
+    #[must_use]
+    pub fn with_time_format(mut self, time_format: TimeFormat) -> Self {
+        self.time_format = time_format;
+        self
+    }
+}
+
+/// A [`DataFeed`] loaded from CSV text.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CsvFeed {
+    bars: Vec<Bar>,
+}
+
+impl CsvFeed {
+    /// Load a CSV data file.
+    pub fn from_path(path: impl AsRef<Path>, options: &CsvOptions) -> RhaiResultOf<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|err| {
+            ERR::ErrorSystem(format!("Cannot read data file '{}'", path.to_string_lossy()), err.into())
+        })?;
+        Self::parse(&text, options).map_err(|err| match *err {
+            ERR::ErrorSystem(msg, err) => {
+                ERR::ErrorSystem(format!("{} in data file '{}'", msg, path.to_string_lossy()), err).into()
+            }
+            err => err.into(),
+        })
+    }
+
+    /// Parse CSV text.
+    pub fn parse(text: &str, options: &CsvOptions) -> RhaiResultOf<Self> {
+        let mut lines = text.lines()
+            .enumerate()
+            .map(|(n, line)| (n + 1, line))
+            .filter(|(_, line)| !line.trim().is_empty())
+            .peekable();
+
+        let header: Vec<String> = match lines.peek() {
+            Some((_, line)) if options.has_header => {
+                let header = line.split(options.delimiter).map(|name| name.trim().to_lowercase()).collect();
+                lines.next();
+                header
+            }
+            _ => Vec::new(),
+        };
+
+        let mut indices = [None; 6];
+        for (field, column) in Field::ALL.iter().copied().zip(&options.columns) {
+            indices[field as usize] = match column {
+                Column::Index(index) => Some(*index),
+                Column::Name(name) => {
+                    let found = header.iter().position(|h| h.eq_ignore_ascii_case(name.trim()));
+                    if found.is_none() && field != Field::Volume {
+                        return Err(ERR::ErrorSystem(
+                            format!("Cannot find column '{}'", name),
+                            "missing column in CSV header".into(),
+                        ).into());
+                    }
+                    found
+                }
+            };
+        }
+
+        let mut bars = Vec::new();
+        for (line_no, line) in lines {
+            let cells: Vec<&str> = line.split(options.delimiter).map(str::trim).collect();
+            let cell = |field: Field| -> RhaiResultOf<Option<&str>> {
+                match indices[field as usize] {
+                    Some(index) => match cells.get(index) {
+                        Some(cell) => Ok(Some(cell)),
+                        None => Err(ERR::ErrorSystem(
+                            format!("Missing {} on line {}", field.name(), line_no),
+                            format!("no column {}", index).into(),
+                        ).into()),
+                    },
+                    None => Ok(None),
+                }
+            };
+            let number = |field: Field| -> RhaiResultOf<FLOAT> {
+                match cell(field)? {
+                    Some(text) => text.parse().map_err(|err: std::num::ParseFloatError| {
+                        ERR::ErrorSystem(format!("Invalid {} on line {}", field.name(), line_no), err.into()).into()
+                    }),
+                    None => Ok(0.0),
+                }
+            };
+
+            let time = cell(Field::Time)?.unwrap_or_default();
+            let time = options.time_format.parse(time).map_err(|err| {
+                ERR::ErrorSystem(format!("Invalid time on line {}", line_no), err.into())
+            })?;
+
+            bars.push(Bar::new(
+                time,
+                number(Field::Open)?,
+                number(Field::High)?,
+                number(Field::Low)?,
+                number(Field::Close)?,
+                number(Field::Volume)?,
+            ));
+        }
+
+        Ok(Self { bars })
+    }
+
+    /// The loaded bars, oldest first.
+    #[inline]
+    #[must_use]
+    pub fn bars(&self) -> &[Bar] {
+        &self.bars
+    }
+}
+
+impl DataFeed for CsvFeed {
+    #[inline]
+    fn len(&self) -> usize {
+        self.bars.len()
+    }
+
+    #[inline]
+    fn bar(&self, index: usize) -> Option<Bar> {
+        self.bars.get(index).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_default_columns() {
+        let text = "Time,Open,High,Low,Close,Volume\n1000,1,2,0.5,1.5,10\n\n2000,1.5,3,1,2.5,20\n";
+        let feed = CsvFeed::parse(text, &CsvOptions::new()).unwrap();
+        assert_eq!(feed.len(), 2);
+        assert_eq!(feed.bar(1), Some(Bar::new(2000, 1.5, 3.0, 1.0, 2.5, 20.0)));
+    }
+
+    #[test]
+    fn test_csv_custom_columns() {
+        let options = CsvOptions::new()
+            .with_delimiter(';')
+            .with_header(false)
+            .with_column(Field::Time, 0)
+            .with_column(Field::Open, 1)
+            .with_column(Field::High, 2)
+            .with_column(Field::Low, 3)
+            .with_column(Field::Close, 4)
+            .with_column(Field::Volume, 5)
+            .with_time_format(TimeFormat::Pattern("%Y-%m-%d %H:%M".into()));
+        let feed = CsvFeed::parse("2024-03-01 09:30;1;2;0.5;1.5;10", &options).unwrap();
+        assert_eq!(feed.bars()[0].time, 1_709_285_400_000);
+        assert_eq!(feed.bars()[0].volume, 10.0);
+    }
+
+    #[test]
+    fn test_csv_errors() {
+        let err = CsvFeed::parse("time,open,high,low\n1,1,1,1", &CsvOptions::new()).unwrap_err();
+        assert!(err.to_string().contains("Cannot find column 'close'"));
+
+        let err = CsvFeed::parse("time,open,high,low,close\n1,1,1,1,x", &CsvOptions::new()).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid close on line 2"));
+    }
+
+    #[test]
+    fn test_time_format() {
+        assert_eq!(TimeFormat::UnixSeconds.parse("86400"), Ok(86_400_000));
+        assert_eq!(TimeFormat::Pattern("%Y-%m-%d".into()).parse("1970-01-02"), Ok(86_400_000));
+        assert_eq!(TimeFormat::Pattern("%Y%m%d".into()).parse("20000301"), Ok(951_868_800_000));
+        assert!(TimeFormat::Pattern("%Y-%m-%d".into()).parse("2024/01/01").is_err());
+    }
+}
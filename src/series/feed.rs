@@ -0,0 +1,42 @@
+use crate::series::Bar;
+
+/// A source of OHLCV bars for running scripts.
+///
+/// Bars are indexed from zero, oldest first.
+pub trait DataFeed {
+    /// Number of bars available.
+    fn len(&self) -> usize;
+
+    /// Is the feed empty?
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the bar at `index`, or [`None`] if out of range.
+    fn bar(&self, index: usize) -> Option<Bar>;
+}
+
+impl DataFeed for [Bar] {
+    #[inline]
+    fn len(&self) -> usize {
+        <[Bar]>::len(self)
+    }
+
+    #[inline]
+    fn bar(&self, index: usize) -> Option<Bar> {
+        self.get(index).copied()
+    }
+}
+
+impl DataFeed for Vec<Bar> {
+    #[inline]
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    #[inline]
+    fn bar(&self, index: usize) -> Option<Bar> {
+        self.get(index).copied()
+    }
+}
@@ -0,0 +1,96 @@
+use std::ops::Index;
+use crate::types::Dynamic;
+
+/// The values a script variable took on every bar, oldest first.
+///
+/// Bars on which the variable had no value hold `na`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Series {
+    values: Vec<Dynamic>,
+}
+
+impl Series {
+    /// Create an empty [`Series`].
+    #[inline]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { values: Vec::new() }
+    }
+
+    /// Number of bars recorded.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Is nothing recorded yet?
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Record the value on `bar`, filling any skipped bars with `na`.
+    pub fn set(&mut self, bar: usize, value: Dynamic) {
+        if self.values.len() <= bar {
+            self.values.resize(bar + 1, Dynamic::NA);
+        }
+        self.values[bar] = value;
+    }
+
+    /// Get the value `offset` bars before `bar`, or `na` if out of range.
+    pub fn get(&self, bar: usize, offset: usize) -> Dynamic {
+        bar.checked_sub(offset)
+            .and_then(|index| self.values.get(index))
+            .cloned()
+            .unwrap_or(Dynamic::NA)
+    }
+
+    /// The most recent value, if any.
+    #[inline]
+    #[must_use]
+    pub fn last(&self) -> Option<&Dynamic> {
+        self.values.last()
+    }
+
+    /// Iterate the values, oldest first.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &Dynamic> {
+        self.values.iter()
+    }
+}
+
+impl Index<usize> for Series {
+    type Output = Dynamic;
+
+    #[inline]
+    fn index(&self, bar: usize) -> &Dynamic {
+        &self.values[bar]
+    }
+}
+
+impl From<Vec<Dynamic>> for Series {
+    #[inline]
+    fn from(values: Vec<Dynamic>) -> Self {
+        Self { values }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_series_history() {
+        let mut series = Series::new();
+        series.set(0, Dynamic::Int(1));
+        series.set(2, Dynamic::Int(3));
+        assert_eq!(series.len(), 3);
+        assert_eq!(series[1], Dynamic::NA);
+        assert_eq!(series.get(2, 0), Dynamic::Int(3));
+        assert_eq!(series.get(2, 2), Dynamic::Int(1));
+        assert_eq!(series.get(2, 3), Dynamic::NA);
+        assert_eq!(series.get(5, 1), Dynamic::NA);
+    }
+}
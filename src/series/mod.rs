@@ -0,0 +1,10 @@
+pub mod bar;
+pub mod csv;
+pub mod feed;
+pub mod history;
+pub(crate) mod state;
+
+pub use bar::Bar;
+pub use csv::{Column, CsvFeed, CsvOptions, Field, TimeFormat};
+pub use feed::DataFeed;
+pub use history::Series;
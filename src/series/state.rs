@@ -0,0 +1,45 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use crate::series::Series;
+use crate::types::Dynamic;
+use crate::types::scope::CallSite;
+
+/// Bar-by-bar execution state, shared by a script and the functions it calls.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct BarState {
+    /// Index of the bar being executed.
+    pub(crate) index: Cell<usize>,
+    /// Committed history of every script variable.
+    pub(crate) history: RefCell<HashMap<String, Series>>,
+    /// History of `expr[n]` references on anything but a top-level script variable, keyed by
+    /// call site.
+    pub(crate) sites: RefCell<HashMap<CallSite, Series>>,
+    /// Current value of every `var` variable, keyed by the call site of its declaration.
+    pub(crate) persistent: RefCell<HashMap<CallSite, Dynamic>>,
+}
+
+impl BarState {
+    /// Value of variable `name` `offset` bars before the current one.
+    pub(crate) fn variable(&self, name: &str, offset: usize) -> Option<Dynamic> {
+        self.history.borrow().get(name).map(|series| series.get(self.index.get(), offset))
+    }
+
+    /// Record `value` for `site` on the current bar, returning its value `offset` bars ago.
+    pub(crate) fn site(&self, site: CallSite, value: Dynamic, offset: usize) -> Dynamic {
+        let bar = self.index.get();
+        let mut sites = self.sites.borrow_mut();
+        let series = sites.entry(site).or_default();
+        series.set(bar, value);
+        series.get(bar, offset)
+    }
+
+    /// Value the `var` variable declared at `site` carries over from the previous bars, if any.
+    pub(crate) fn persistent(&self, site: &CallSite) -> Option<Dynamic> {
+        self.persistent.borrow().get(site).cloned()
+    }
+
+    /// Keep `value` as the current value of the `var` variable declared at `site`.
+    pub(crate) fn persist(&self, site: CallSite, value: Dynamic) {
+        self.persistent.borrow_mut().insert(site, value);
+    }
+}
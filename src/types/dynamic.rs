@@ -0,0 +1,397 @@
+//! Helper module that implements the [`Dynamic`] type.
+
+use std::any::{type_name, Any, TypeId};
+use std::fmt;
+use std::rc::Rc;
+use crate::types::FnPtr;
+use crate::{Array, Map, FLOAT, INT};
+
+/// Dynamic type containing any value produced by a script.
+#[derive(Clone)]
+#[must_use]
+pub enum Dynamic {
+    /// A missing value (`na`).
+    Na,
+    /// A boolean value.
+    Bool(bool),
+    /// An integer value.
+    Int(INT),
+    /// A floating-point value.
+    Float(FLOAT),
+    /// A string value.
+    Str(String),
+    /// An array (or tuple) of values.
+    Array(Array),
+    /// An object map of values keyed by name.
+    Map(Map),
+    /// A pointer to a function.
+    FnPtr(FnPtr),
+    /// A host-provided value of any other type, together with its type name.
+    Custom(Rc<dyn Any>, &'static str),
+}
+
+impl Dynamic {
+    /// A [`Dynamic`] containing `na`.
+    pub const NA: Self = Self::Na;
+    /// A [`Dynamic`] containing `true`.
+    pub const TRUE: Self = Self::Bool(true);
+    /// A [`Dynamic`] containing `false`.
+    pub const FALSE: Self = Self::Bool(false);
+
+    /// Get the name of the type of the value held by this [`Dynamic`].
+    #[must_use]
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Na => "na",
+            Self::Bool(_) => "bool",
+            Self::Int(_) => "int",
+            Self::Float(_) => "float",
+            Self::Str(_) => "string",
+            Self::Array(_) => "array",
+            Self::Map(_) => "map",
+            Self::FnPtr(_) => "Fn",
+            Self::Custom(_, name) => name,
+        }
+    }
+
+    /// Is the value `na`?
+    #[inline(always)]
+    #[must_use]
+    pub const fn is_na(&self) -> bool {
+        matches!(self, Self::Na)
+    }
+
+    /// Cast the [`Dynamic`] as a [`bool`].
+    /// Returns the name of the actual type if the cast fails.
+    #[inline]
+    pub fn as_bool(&self) -> Result<bool, &'static str> {
+        match self {
+            Self::Bool(b) => Ok(*b),
+            _ => Err(self.type_name()),
+        }
+    }
+
+    /// Cast the [`Dynamic`] as the system integer type [`INT`].
+    /// Returns the name of the actual type if the cast fails.
+    #[inline]
+    pub fn as_int(&self) -> Result<INT, &'static str> {
+        match self {
+            Self::Int(n) => Ok(*n),
+            _ => Err(self.type_name()),
+        }
+    }
+
+    /// Cast the [`Dynamic`] as the system floating-point type [`FLOAT`].
+    /// Returns the name of the actual type if the cast fails.
+    #[inline]
+    pub fn as_float(&self) -> Result<FLOAT, &'static str> {
+        match self {
+            Self::Float(n) => Ok(*n),
+            _ => Err(self.type_name()),
+        }
+    }
+
+    /// Cast the [`Dynamic`] as a number, promoting integers to [`FLOAT`].
+    /// Returns the name of the actual type if the cast fails.
+    #[inline]
+    pub fn as_number(&self) -> Result<FLOAT, &'static str> {
+        match self {
+            Self::Int(n) => Ok(*n as FLOAT),
+            Self::Float(n) => Ok(*n),
+            _ => Err(self.type_name()),
+        }
+    }
+
+    /// Convert the [`Dynamic`] into a [`String`].
+    /// Returns the name of the actual type if the cast fails.
+    #[inline]
+    pub fn into_string(self) -> Result<String, &'static str> {
+        match self {
+            Self::Str(s) => Ok(s),
+            _ => Err(self.type_name()),
+        }
+    }
+
+    /// Convert the [`Dynamic`] into an [`Array`].
+    /// Returns the name of the actual type if the cast fails.
+    #[inline]
+    pub fn into_array(self) -> Result<Array, &'static str> {
+        match self {
+            Self::Array(a) => Ok(a),
+            _ => Err(self.type_name()),
+        }
+    }
+
+    /// Convert the [`Dynamic`] into a [`Map`].
+    /// Returns the name of the actual type if the cast fails.
+    #[inline]
+    pub fn into_map(self) -> Result<Map, &'static str> {
+        match self {
+            Self::Map(m) => Ok(m),
+            _ => Err(self.type_name()),
+        }
+    }
+
+    /// Create a [`Dynamic`] from any type.
+    ///
+    /// Standard types ([`INT`], [`FLOAT`], [`bool`], [`String`], [`Array`], [`Map`], [`FnPtr`])
+    /// are stored as their own variants; all other types are stored as custom objects.
+    pub fn from<T: Any + Clone>(value: T) -> Self {
+        let value: Box<dyn Any> = Box::new(value);
+
+        let value = match value.downcast::<Self>() {
+            Ok(d) => return *d,
+            Err(v) => v,
+        };
+        let value = match value.downcast::<INT>() {
+            Ok(n) => return Self::Int(*n),
+            Err(v) => v,
+        };
+        let value = match value.downcast::<FLOAT>() {
+            Ok(n) => return Self::Float(*n),
+            Err(v) => v,
+        };
+        let value = match value.downcast::<bool>() {
+            Ok(b) => return Self::Bool(*b),
+            Err(v) => v,
+        };
+        let value = match value.downcast::<String>() {
+            Ok(s) => return Self::Str(*s),
+            Err(v) => v,
+        };
+        let value = match value.downcast::<&str>() {
+            Ok(s) => return Self::Str(s.to_string()),
+            Err(v) => v,
+        };
+        let value = match value.downcast::<Array>() {
+            Ok(a) => return Self::Array(*a),
+            Err(v) => v,
+        };
+        let value = match value.downcast::<Map>() {
+            Ok(m) => return Self::Map(*m),
+            Err(v) => v,
+        };
+        let value = match value.downcast::<FnPtr>() {
+            Ok(f) => return Self::FnPtr(*f),
+            Err(v) => v,
+        };
+        let value = match value.downcast::<()>() {
+            Ok(_) => return Self::Na,
+            Err(v) => v,
+        };
+
+        Self::Custom(value.into(), type_name::<T>())
+    }
+
+    /// Convert the [`Dynamic`] value into a specific type.
+    ///
+    /// Returns [`None`] if the value is not of type `T`.
+    /// Casting to [`Dynamic`] always succeeds.
+    #[must_use]
+    pub fn try_cast<T: Any + Clone>(self) -> Option<T> {
+        if TypeId::of::<T>() == TypeId::of::<Self>() {
+            return (Box::new(self) as Box<dyn Any>).downcast().ok().map(|v| *v);
+        }
+
+        let value: Box<dyn Any> = match self {
+            Self::Na => Box::new(()),
+            Self::Bool(b) => Box::new(b),
+            Self::Int(n) => Box::new(n),
+            Self::Float(n) => Box::new(n),
+            Self::Str(s) => Box::new(s),
+            Self::Array(a) => Box::new(a),
+            Self::Map(m) => Box::new(m),
+            Self::FnPtr(f) => Box::new(f),
+            Self::Custom(value, _) => return value.downcast_ref::<T>().cloned(),
+        };
+
+        value.downcast().ok().map(|v| *v)
+    }
+
+    /// Convert the [`Dynamic`] value into a specific type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cast fails (e.g. the type of the actual value is not `T`).
+    #[must_use]
+    pub fn cast<T: Any + Clone>(self) -> T {
+        let self_type_name = self.type_name();
+
+        self.try_cast::<T>().unwrap_or_else(|| {
+            panic!(
+                "cannot cast {} value to {}",
+                self_type_name,
+                type_name::<T>()
+            )
+        })
+    }
+
+    /// Is the value of type `T`?
+    #[must_use]
+    pub fn is<T: Any + Clone>(&self) -> bool {
+        self.clone().try_cast::<T>().is_some()
+    }
+}
+
+impl Default for Dynamic {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::Na
+    }
+}
+
+impl PartialEq for Dynamic {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Na, Self::Na) => true,
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Int(a), Self::Int(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a == b,
+            (Self::Str(a), Self::Str(b)) => a == b,
+            (Self::Array(a), Self::Array(b)) => a == b,
+            (Self::Map(a), Self::Map(b)) => a == b,
+            (Self::FnPtr(a), Self::FnPtr(b)) => a == b,
+            (Self::Custom(a, _), Self::Custom(b, _)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Dynamic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Na => f.write_str("na"),
+            Self::Bool(b) => fmt::Display::fmt(b, f),
+            Self::Int(n) => fmt::Display::fmt(n, f),
+            Self::Float(n) => fmt::Debug::fmt(n, f),
+            Self::Str(s) => f.write_str(s),
+            Self::Array(a) => {
+                f.write_str("[")?;
+                for (i, item) in a.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{item:?}")?;
+                }
+                f.write_str("]")
+            }
+            Self::Map(m) => {
+                f.write_str("#{")?;
+                for (i, (key, value)) in m.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{key}: {value:?}")?;
+                }
+                f.write_str("}")
+            }
+            Self::FnPtr(func) => fmt::Display::fmt(func, f),
+            Self::Custom(_, name) => write!(f, "<{name}>"),
+        }
+    }
+}
+
+impl fmt::Debug for Dynamic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Str(s) => fmt::Debug::fmt(s, f),
+            _ => fmt::Display::fmt(self, f),
+        }
+    }
+}
+
+impl From<()> for Dynamic {
+    #[inline(always)]
+    fn from(_: ()) -> Self {
+        Self::Na
+    }
+}
+impl From<bool> for Dynamic {
+    #[inline(always)]
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+impl From<INT> for Dynamic {
+    #[inline(always)]
+    fn from(value: INT) -> Self {
+        Self::Int(value)
+    }
+}
+impl From<FLOAT> for Dynamic {
+    #[inline(always)]
+    fn from(value: FLOAT) -> Self {
+        Self::Float(value)
+    }
+}
+impl From<String> for Dynamic {
+    #[inline(always)]
+    fn from(value: String) -> Self {
+        Self::Str(value)
+    }
+}
+impl From<&str> for Dynamic {
+    #[inline(always)]
+    fn from(value: &str) -> Self {
+        Self::Str(value.to_string())
+    }
+}
+impl From<Array> for Dynamic {
+    #[inline(always)]
+    fn from(value: Array) -> Self {
+        Self::Array(value)
+    }
+}
+impl From<Map> for Dynamic {
+    #[inline(always)]
+    fn from(value: Map) -> Self {
+        Self::Map(value)
+    }
+}
+impl From<FnPtr> for Dynamic {
+    #[inline(always)]
+    fn from(value: FnPtr) -> Self {
+        Self::FnPtr(value)
+    }
+}
+impl<T: Into<Dynamic>> From<Option<T>> for Dynamic {
+    /// [`None`] becomes `na`.
+    #[inline]
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Na, Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Point {
+        x: INT,
+        y: INT,
+    }
+
+    #[test]
+    fn test_dynamic_standard_types() {
+        assert_eq!(Dynamic::from(42 as INT), Dynamic::Int(42));
+        assert_eq!(Dynamic::from("hello"), Dynamic::Str("hello".into()));
+        assert_eq!(Dynamic::from(vec![Dynamic::from(1 as INT)]).type_name(), "array");
+        assert_eq!(Dynamic::Float(1.5).try_cast::<FLOAT>(), Some(1.5));
+        assert_eq!(Dynamic::Int(1).try_cast::<FLOAT>(), None);
+        assert_eq!(Dynamic::Int(1).as_number(), Ok(1.0));
+        assert_eq!(Dynamic::Bool(true).as_int(), Err("bool"));
+        assert_eq!(Dynamic::NA, None::<INT>.into());
+        assert_eq!(Dynamic::TRUE.try_cast::<Dynamic>(), Some(Dynamic::TRUE));
+    }
+
+    #[test]
+    fn test_dynamic_custom_type() {
+        let value = Dynamic::from(Point { x: 1, y: 2 });
+        assert!(value.type_name().ends_with("Point"));
+        assert!(value.is::<Point>());
+        assert_eq!(value.clone().cast::<Point>(), Point { x: 1, y: 2 });
+        assert_eq!(value, value.clone());
+        assert_eq!(value.try_cast::<INT>(), None);
+    }
+}
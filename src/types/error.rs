@@ -6,6 +6,7 @@ use std::error::Error;
 use std::fmt;
 #[cfg(feature = "no_std")]
 use std::prelude::v1::*;
+use crate::types::{Dynamic, ParseError, ParseErrorType, Position};
 
 /// Evaluation result.
 ///
@@ -29,9 +30,35 @@ pub enum EvalAltResult {
     /// System error. Wrapped values are the error message and the internal error.
     #[cfg(feature = "sync")]
     ErrorSystem(String, Box<dyn Error + Send + Sync>),
-    ErrorRuntime(String, String),
 
+    /// Syntax error.
+    ErrorParsing(ParseErrorType, Position),
+    /// Several syntax errors found in the same script, in order of appearance.
+    ErrorParsingMultiple(Vec<ParseError>),
 
+    /// Access to a variable that is not defined. Wrapped value is the variable name.
+    ErrorVariableNotFound(String, Position),
+    /// Call to an unknown function. Wrapped value is the function name.
+    ErrorFunctionNotFound(String, Position),
+    /// Assignment to a constant variable. Wrapped value is the variable name.
+    ErrorAssignmentToConstant(String, Position),
+    /// Data is not of the required type.
+    /// Wrapped values are the type requested and type of the actual result.
+    ErrorMismatchDataType(String, String, Position),
+    /// Returned type is not the same as the required output type.
+    /// Wrapped values are the type requested and type of the actual result.
+    ErrorMismatchOutputType(String, String, Position),
+    /// Arithmetic error encountered. Wrapped value is the error message.
+    ErrorArithmetic(String, Position),
+    /// Run-time error encountered. Wrapped value is the error message.
+    ErrorRuntime(String, Position),
+    /// Call stack over maximum limit.
+    ErrorStackOverflow(Position),
+
+    /// Breaking out of loops - not an error if within a loop.
+    /// The wrapped value, if true, means breaking clean out of the loop (i.e. a `break` statement).
+    /// The wrapped value, if false, means breaking the current context (i.e. a `continue` statement).
+    LoopBreak(bool, Dynamic, Position),
 }
 
 impl Error for EvalAltResult {}
@@ -41,9 +68,39 @@ impl fmt::Display for EvalAltResult {
         match self {
             Self::ErrorSystem(s, err) if s.is_empty() => write!(f, "{err}")?,
             Self::ErrorSystem(s, err) => write!(f, "{s}: {err}")?,
-            Self::ErrorRuntime(s, err) => write!(f, "{s}: {err}")?,
 
+            Self::ErrorParsing(p, ..) => write!(f, "Syntax error: {p}")?,
+            Self::ErrorParsingMultiple(errors) => {
+                write!(f, "{} syntax errors:", errors.len())?;
+                for err in errors {
+                    write!(f, "\n  {err}")?;
+                }
+                // Each error already shows its own position
+                return Ok(());
+            }
+
+            Self::ErrorVariableNotFound(s, ..) => write!(f, "Variable not found: {s}")?,
+            Self::ErrorFunctionNotFound(s, ..) => write!(f, "Function not found: {s}")?,
+            Self::ErrorAssignmentToConstant(s, ..) => write!(f, "Cannot modify constant: {s}")?,
+            Self::ErrorMismatchDataType(s, r, ..) => {
+                write!(f, "Data type incorrect: {r} (expecting {s})")?
+            }
+            Self::ErrorMismatchOutputType(s, r, ..) => {
+                write!(f, "Output type incorrect: {r} (expecting {s})")?
+            }
+            Self::ErrorArithmetic(s, ..) if s.is_empty() => f.write_str("Arithmetic error")?,
+            Self::ErrorArithmetic(s, ..) => f.write_str(s)?,
+            Self::ErrorRuntime(s, ..) if s.is_empty() => f.write_str("Runtime error")?,
+            Self::ErrorRuntime(s, ..) => f.write_str(s)?,
+            Self::ErrorStackOverflow(..) => f.write_str("Stack overflow")?,
+
+            Self::LoopBreak(true, ..) => f.write_str("'break' must be within a loop")?,
+            Self::LoopBreak(false, ..) => f.write_str("'continue' must be within a loop")?,
+        }
 
+        // Do not write any position if None
+        if !self.position().is_none() {
+            write!(f, " ({})", self.position())?;
         }
 
         Ok(())
@@ -54,7 +111,7 @@ impl<T: AsRef<str>> From<T> for EvalAltResult {
     #[cold]
     #[inline(never)]
     fn from(err: T) -> Self {
-        Self::ErrorRuntime(err.as_ref().to_string().into(), err.as_ref().to_string().into())
+        Self::ErrorRuntime(err.as_ref().to_string(), Position::NONE)
     }
 }
 
@@ -69,26 +126,94 @@ impl<T: AsRef<str>> From<T> for Box<EvalAltResult> {
 impl EvalAltResult {
     /// Is this a pseudo error?  A pseudo error is one that does not occur naturally.
     ///
-    /// [`LoopBreak`][EvalAltResult::LoopBreak], [`Return`][EvalAltResult::Return] and [`Exit`][EvalAltResult::Exit] are pseudo errors.
-    // #[cold]
-    // #[inline(never)]
-    // #[must_use]
-    // pub const fn is_pseudo_error(&self) -> bool {
-    //     matches!(
-    //         self,
-    //         Self::LoopBreak(..) | Self::Return(..) | Self::Exit(..)
-    //     )
-    // }
+    /// [`LoopBreak`][EvalAltResult::LoopBreak] is a pseudo error.
+    #[cold]
+    #[inline(never)]
+    #[must_use]
+    pub const fn is_pseudo_error(&self) -> bool {
+        matches!(self, Self::LoopBreak(..))
+    }
     /// Can this error be caught?
     #[cold]
     #[inline(never)]
     #[must_use]
     pub const fn is_catchable(&self) -> bool {
         match self {
-            Self::ErrorSystem(..) => false,
-            Self::ErrorRuntime(..) => true,
+            Self::ErrorSystem(..) | Self::ErrorParsing(..) | Self::ErrorParsingMultiple(..) => false,
+            Self::LoopBreak(..) | Self::ErrorStackOverflow(..) => false,
+            Self::ErrorVariableNotFound(..)
+            | Self::ErrorFunctionNotFound(..)
+            | Self::ErrorAssignmentToConstant(..)
+            | Self::ErrorMismatchDataType(..)
+            | Self::ErrorMismatchOutputType(..)
+            | Self::ErrorArithmetic(..)
+            | Self::ErrorRuntime(..) => true,
+
+        }
+    }
+
+    /// Get the [position][Position] of this error.
+    #[must_use]
+    pub fn position(&self) -> Position {
+        match self {
+            Self::ErrorSystem(..) => Position::NONE,
+            Self::ErrorParsingMultiple(errors) => match errors.first() {
+                Some(err) => err.position(),
+                None => Position::NONE,
+            },
 
+            Self::ErrorParsing(.., pos)
+            | Self::ErrorVariableNotFound(.., pos)
+            | Self::ErrorFunctionNotFound(.., pos)
+            | Self::ErrorAssignmentToConstant(.., pos)
+            | Self::ErrorMismatchDataType(.., pos)
+            | Self::ErrorMismatchOutputType(.., pos)
+            | Self::ErrorArithmetic(.., pos)
+            | Self::ErrorRuntime(.., pos)
+            | Self::ErrorStackOverflow(pos)
+            | Self::LoopBreak(.., pos) => *pos,
         }
     }
 
+    /// Override the [position][Position] of this error.
+    pub fn set_position(&mut self, new_position: Position) -> &mut Self {
+        match self {
+            Self::ErrorSystem(..) | Self::ErrorParsingMultiple(..) => (),
+
+            Self::ErrorParsing(.., pos)
+            | Self::ErrorVariableNotFound(.., pos)
+            | Self::ErrorFunctionNotFound(.., pos)
+            | Self::ErrorAssignmentToConstant(.., pos)
+            | Self::ErrorMismatchDataType(.., pos)
+            | Self::ErrorMismatchOutputType(.., pos)
+            | Self::ErrorArithmetic(.., pos)
+            | Self::ErrorRuntime(.., pos)
+            | Self::ErrorStackOverflow(pos)
+            | Self::LoopBreak(.., pos) => *pos = new_position,
+        }
+        self
+    }
+
+    /// Create an error from the syntax errors of a script.
+    ///
+    /// A single error is reported as-is; several are kept together so all can be shown at once.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn from_parse_errors(mut errors: Vec<ParseError>) -> Box<Self> {
+        if errors.len() == 1 {
+            errors.remove(0).into()
+        } else {
+            Self::ErrorParsingMultiple(errors).into()
+        }
+    }
+
+    /// Consume the current [`EvalAltResult`] and return a new one with the specified [`Position`]
+    /// if the current position is [`Position::NONE`].
+    #[inline(never)]
+    pub(crate) fn fill_position(mut self: Box<Self>, new_position: Position) -> Box<Self> {
+        if self.position().is_none() {
+            self.set_position(new_position);
+        }
+        self
+    }
 }
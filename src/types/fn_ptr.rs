@@ -0,0 +1,88 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use crate::ast::function::Function;
+use crate::types::Scope;
+
+/// A function pointer: a reference to a script-defined function that can be stored in a
+/// variable, passed as an argument and called later.
+///
+/// A pointer created by the script is a closure: it keeps the function definition together
+/// with the scope it was defined in, whose variables it captures by reference.
+#[derive(Clone)]
+pub struct FnPtr {
+    name: String,
+    closure: Option<Rc<(Function, Scope)>>,
+}
+
+impl FnPtr {
+    /// Create a new function pointer to the function named `name`.
+    #[inline(always)]
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), closure: None }
+    }
+
+    /// Create a closure over `function` defined in the scope `environ`.
+    #[must_use]
+    pub(crate) fn closure(function: Function, environ: Scope) -> Self {
+        Self { name: function.name.clone(), closure: Some(Rc::new((function, environ))) }
+    }
+
+    /// Get the name of the function; empty for an anonymous function.
+    #[inline(always)]
+    #[must_use]
+    pub fn fn_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Is this a pointer to an anonymous function?
+    #[inline(always)]
+    #[must_use]
+    pub fn is_anonymous(&self) -> bool {
+        self.name.is_empty()
+    }
+
+    /// Function definition and the scope it closes over, if this is a closure.
+    #[inline(always)]
+    #[must_use]
+    pub(crate) fn closure_def(&self) -> Option<&(Function, Scope)> {
+        self.closure.as_deref()
+    }
+}
+
+impl PartialEq for FnPtr {
+    /// Pointers are equal if they refer to the same function by name, or are the same closure.
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.closure, &other.closure) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => self.name == other.name,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for FnPtr {}
+
+impl Hash for FnPtr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+impl fmt::Debug for FnPtr {
+    // The captured scope is left out as it may well contain this very closure
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnPtr").field("name", &self.name).finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for FnPtr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_anonymous() {
+            f.write_str("Fn(<anonymous>)")
+        } else {
+            write!(f, "Fn({})", self.name)
+        }
+    }
+}
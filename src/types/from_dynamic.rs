@@ -0,0 +1,186 @@
+use std::collections::{BTreeMap, HashMap};
+use crate::types::{Dynamic, FnPtr, Position};
+use crate::{RhaiResultOf, ERR, FLOAT, INT};
+
+/// Conversion of a script value into a Rust type, as done for the results handed back to the
+/// host by [`Engine::eval`][crate::Engine::eval].
+///
+/// Arrays convert to a [`Vec`] or a tuple of matching length, maps to a [`BTreeMap`] or a
+/// [`HashMap`] keyed by [`String`], and `na` to [`None`] for an [`Option`]. Integers are promoted
+/// when a [`FLOAT`] is asked for.
+///
+/// Custom types can take part by implementing this trait with [`Dynamic::try_cast`].
+pub trait FromDynamic: Sized {
+    /// Name of the type in error messages, in the terms of [`Dynamic::type_name`].
+    fn type_name() -> String;
+
+    /// Convert `value`, returning [`None`] if it does not hold this type.
+    fn from_dynamic(value: Dynamic) -> Option<Self>;
+}
+
+/// Convert the result of a script into `T`.
+pub(crate) fn cast_output<T: FromDynamic>(value: Dynamic) -> RhaiResultOf<T> {
+    let actual = value.type_name();
+    T::from_dynamic(value)
+        .ok_or_else(|| ERR::ErrorMismatchOutputType(T::type_name(), actual.into(), Position::NONE).into())
+}
+
+macro_rules! from_variant {
+    ($type:ty, $name:literal, $variant:ident) => {
+        impl FromDynamic for $type {
+            fn type_name() -> String {
+                $name.into()
+            }
+
+            fn from_dynamic(value: Dynamic) -> Option<Self> {
+                match value {
+                    Dynamic::$variant(value) => Some(value),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+from_variant!(bool, "bool", Bool);
+from_variant!(INT, "int", Int);
+from_variant!(String, "string", Str);
+from_variant!(FnPtr, "Fn", FnPtr);
+
+impl FromDynamic for Dynamic {
+    fn type_name() -> String {
+        "any".into()
+    }
+
+    fn from_dynamic(value: Dynamic) -> Option<Self> {
+        Some(value)
+    }
+}
+
+impl FromDynamic for () {
+    fn type_name() -> String {
+        "na".into()
+    }
+
+    fn from_dynamic(value: Dynamic) -> Option<Self> {
+        value.is_na().then_some(())
+    }
+}
+
+impl FromDynamic for FLOAT {
+    fn type_name() -> String {
+        "float".into()
+    }
+
+    fn from_dynamic(value: Dynamic) -> Option<Self> {
+        value.as_number().ok()
+    }
+}
+
+impl<T: FromDynamic> FromDynamic for Option<T> {
+    fn type_name() -> String {
+        T::type_name()
+    }
+
+    fn from_dynamic(value: Dynamic) -> Option<Self> {
+        match value {
+            Dynamic::Na => Some(None),
+            value => T::from_dynamic(value).map(Some),
+        }
+    }
+}
+
+impl<T: FromDynamic> FromDynamic for Vec<T> {
+    fn type_name() -> String {
+        format!("array of {}", T::type_name())
+    }
+
+    fn from_dynamic(value: Dynamic) -> Option<Self> {
+        match value {
+            Dynamic::Array(items) => items.into_iter().map(T::from_dynamic).collect(),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromDynamic> FromDynamic for BTreeMap<String, T> {
+    fn type_name() -> String {
+        format!("map of {}", T::type_name())
+    }
+
+    fn from_dynamic(value: Dynamic) -> Option<Self> {
+        match value {
+            Dynamic::Map(map) => map.into_iter().map(|(key, value)| Some((key, T::from_dynamic(value)?))).collect(),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromDynamic> FromDynamic for HashMap<String, T> {
+    fn type_name() -> String {
+        format!("map of {}", T::type_name())
+    }
+
+    fn from_dynamic(value: Dynamic) -> Option<Self> {
+        match value {
+            Dynamic::Map(map) => map.into_iter().map(|(key, value)| Some((key, T::from_dynamic(value)?))).collect(),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! from_tuple {
+    ($($item:ident),+) => {
+        impl<$($item: FromDynamic),+> FromDynamic for ($($item,)+) {
+            fn type_name() -> String {
+                let names: Vec<String> = vec![$($item::type_name()),+];
+                format!("[{}]", names.join(", "))
+            }
+
+            fn from_dynamic(value: Dynamic) -> Option<Self> {
+                let items = match value {
+                    Dynamic::Array(items) if items.len() == from_tuple!(@count $($item)+) => items,
+                    _ => return None,
+                };
+                let mut items = items.into_iter();
+                Some(($($item::from_dynamic(items.next()?)?,)+))
+            }
+        }
+    };
+    (@count $($item:ident)+) => {
+        <[()]>::len(&[$(from_tuple!(@unit $item)),+])
+    };
+    (@unit $item:ident) => {
+        ()
+    };
+}
+
+from_tuple!(A);
+from_tuple!(A, B);
+from_tuple!(A, B, C);
+from_tuple!(A, B, C, D);
+from_tuple!(A, B, C, D, E);
+from_tuple!(A, B, C, D, E, F);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Map;
+
+    #[test]
+    fn test_from_dynamic() {
+        let array = Dynamic::Array(vec![Dynamic::Int(1), Dynamic::Float(2.5), Dynamic::NA]);
+        assert_eq!(Vec::<Option<FLOAT>>::from_dynamic(array.clone()), Some(vec![Some(1.0), Some(2.5), None]));
+        assert_eq!(Vec::<INT>::from_dynamic(array.clone()), None);
+        assert_eq!(<(INT, FLOAT, ())>::from_dynamic(array.clone()), Some((1, 2.5, ())));
+        assert_eq!(<(INT, FLOAT)>::from_dynamic(array), None);
+
+        let map: Map = vec![("a".to_string(), Dynamic::Int(1))].into_iter().collect();
+        let map = HashMap::<String, INT>::from_dynamic(Dynamic::Map(map)).unwrap();
+        assert_eq!(map["a"], 1);
+
+        assert_eq!(<(INT, String)>::type_name(), "[int, string]");
+        let err = cast_output::<Vec<INT>>(Dynamic::Int(1)).unwrap_err();
+        assert_eq!(err.to_string(), "Output type incorrect: int (expecting array of int)");
+    }
+}
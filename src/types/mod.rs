@@ -1,5 +1,17 @@
+pub mod dynamic;
 pub mod error;
+pub mod fn_ptr;
+pub mod from_dynamic;
+pub mod native_fn;
+pub mod parse_error;
+pub mod position;
 pub mod scope;
 
+pub use dynamic::Dynamic;
 pub use error::EvalAltResult;
-pub use scope::Scope;
\ No newline at end of file
+pub use fn_ptr::FnPtr;
+pub use from_dynamic::FromDynamic;
+pub use native_fn::{NativeReturn, RegisterNativeFunction};
+pub use parse_error::{ParseError, ParseErrorType};
+pub use position::{Position, Span};
+pub use scope::Scope;
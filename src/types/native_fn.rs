@@ -0,0 +1,100 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::types::{Dynamic, FromDynamic};
+use crate::RhaiResultOf;
+
+/// Call of a registered function with the arguments of a script, returning [`None`] if they are
+/// not of the types of its parameters.
+type NativeCall = dyn Fn(&[Dynamic]) -> Option<RhaiResultOf<Dynamic>>;
+
+/// A Rust function registered with [`Engine::register_fn`][crate::Engine::register_fn].
+#[derive(Clone)]
+pub struct NativeFunction {
+    /// Number of parameters.
+    pub(crate) arity: usize,
+    func: Rc<NativeCall>,
+}
+
+impl NativeFunction {
+    /// Call the function, returning [`None`] if `args` do not suit its parameters.
+    pub(crate) fn call(&self, args: &[Dynamic]) -> Option<RhaiResultOf<Dynamic>> {
+        if args.len() == self.arity {
+            (self.func)(args)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction").field("arity", &self.arity).finish_non_exhaustive()
+    }
+}
+
+/// Marker for functions returning their value directly.
+pub struct Infallible;
+
+/// Marker for functions returning [`RhaiResultOf`], whose errors are raised in the script.
+pub struct Fallible;
+
+/// Value returned by a registered Rust function.
+pub trait NativeReturn<Marker> {
+    /// Convert into the result of the call.
+    fn into_call_result(self) -> RhaiResultOf<Dynamic>;
+}
+
+impl<T: Into<Dynamic>> NativeReturn<Infallible> for T {
+    #[inline(always)]
+    fn into_call_result(self) -> RhaiResultOf<Dynamic> {
+        Ok(self.into())
+    }
+}
+
+impl<T: Into<Dynamic>> NativeReturn<Fallible> for RhaiResultOf<T> {
+    #[inline(always)]
+    fn into_call_result(self) -> RhaiResultOf<Dynamic> {
+        self.map(Into::into)
+    }
+}
+
+/// A Rust closure or function that can be registered with
+/// [`Engine::register_fn`][crate::Engine::register_fn].
+///
+/// Implemented for `Fn(A, B, ...) -> R` with up to six parameters of types implementing
+/// [`FromDynamic`], returning either a value convertible into [`Dynamic`] or a
+/// [`RhaiResultOf`] of one.
+pub trait RegisterNativeFunction<Args, Marker> {
+    #[doc(hidden)]
+    fn into_native_function(self) -> NativeFunction;
+}
+
+macro_rules! register_native {
+    ($($arg:ident $param:ident),*) => {
+        impl<FN, $($param,)* RET, MARKER> RegisterNativeFunction<($($param,)*), MARKER> for FN
+        where
+            FN: Fn($($param),*) -> RET + 'static,
+            $($param: FromDynamic,)*
+            RET: NativeReturn<MARKER>,
+        {
+            #[allow(unused_mut, unused_variables)]
+            fn into_native_function(self) -> NativeFunction {
+                let arity = <[&str]>::len(&[$(stringify!($param)),*]);
+                let func = move |args: &[Dynamic]| {
+                    let mut args = args.iter().cloned();
+                    $(let $arg = $param::from_dynamic(args.next()?)?;)*
+                    Some(self($($arg),*).into_call_result())
+                };
+                NativeFunction { arity, func: Rc::new(func) }
+            }
+        }
+    };
+}
+
+register_native!();
+register_native!(a A);
+register_native!(a A, b B);
+register_native!(a A, b B, c C);
+register_native!(a A, b B, c C, d D);
+register_native!(a A, b B, c C, d D, e E);
+register_native!(a A, b B, c C, d D, e E, f F);
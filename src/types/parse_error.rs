@@ -0,0 +1,145 @@
+//! Module containing error definitions for the parsing process.
+
+#[cfg(feature = "no_std")]
+use core_error::Error;
+#[cfg(not(feature = "no_std"))]
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "no_std")]
+use std::prelude::v1::*;
+use crate::types::Position;
+use crate::EvalAltResult;
+
+/// Error encountered when parsing a script.
+///
+/// Some errors never appear when certain features are turned on.
+/// They still exist so that the application can turn features on and off without going through
+/// massive code changes to remove/add back enum variants in match statements.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+#[non_exhaustive]
+pub enum ParseErrorType {
+    /// The script ends prematurely.
+    UnexpectedEOF,
+    /// Encountered input that cannot start or continue any construct.
+    /// Wrapped value is the offending token.
+    UnexpectedInput(String),
+    /// A required token is missing.
+    /// Wrapped values are the token and a description of where it was expected.
+    MissingToken(String, String),
+    /// An expression is expected but not found.
+    /// Wrapped value is a description of where it was expected.
+    ExprExpected(String),
+    /// A numeric literal is malformed or out of range.
+    /// Wrapped value is the literal text.
+    MalformedNumber(String),
+    /// A string literal is not closed before the end of the script.
+    UnterminatedString,
+    /// An escape sequence in a string literal is invalid.
+    /// Wrapped value is the escape sequence.
+    MalformedEscapeSequence(String),
+    /// A block comment is not closed before the end of the script.
+    UnterminatedComment,
+    /// A reserved keyword is used as the name of a variable, function or parameter.
+    /// Wrapped value is the keyword.
+    Reserved(String),
+    /// A function definition is missing its body.
+    /// Wrapped value is the function name.
+    FnMissingBody(String),
+    /// An indented block is missing.
+    /// Wrapped value is a description of where it was expected.
+    BlockExpected(String),
+    /// A line is indented deeper than the block it is in.
+    UnexpectedIndent,
+    /// A line is dedented to a level that matches no enclosing block, or mixes tabs and spaces
+    /// differently from the lines before it.
+    InconsistentIndent,
+    /// A positional argument follows a named argument in a function call.
+    PositionalAfterNamed,
+}
+
+impl Error for ParseErrorType {}
+
+impl fmt::Display for ParseErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEOF => f.write_str("unexpected end of script"),
+            Self::UnexpectedInput(s) => write!(f, "unexpected `{s}`"),
+            Self::MissingToken(token, s) => write!(f, "expected `{token}` {s}"),
+            Self::ExprExpected(s) => write!(f, "expected an expression {s}"),
+            Self::MalformedNumber(s) => write!(f, "invalid number literal `{s}`"),
+            Self::UnterminatedString => f.write_str("unterminated string literal"),
+            Self::MalformedEscapeSequence(s) => write!(f, "invalid escape sequence `{s}`"),
+            Self::UnterminatedComment => f.write_str("unterminated block comment"),
+            Self::Reserved(s) => write!(f, "`{s}` is a reserved keyword and cannot be used as a name"),
+            Self::FnMissingBody(s) => write!(f, "expected a body for function `{s}`"),
+            Self::BlockExpected(s) => write!(f, "expected an indented block {s}"),
+            Self::UnexpectedIndent => f.write_str("unexpected indentation"),
+            Self::InconsistentIndent => f.write_str("indentation does not match any outer block"),
+            Self::PositionalAfterNamed => f.write_str("positional arguments must come before named arguments"),
+        }
+    }
+}
+
+impl ParseErrorType {
+    /// Make a [`ParseError`] using the current type and position.
+    #[inline(always)]
+    #[must_use]
+    pub(crate) fn into_err(self, pos: Position) -> ParseError {
+        ParseError(self.into(), pos)
+    }
+}
+
+/// Error when parsing a script.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub struct ParseError(
+    /// Parse error type.
+    pub Box<ParseErrorType>,
+    /// [Position] of the parse error.
+    pub Position,
+);
+
+impl Error for ParseError {}
+
+impl fmt::Display for ParseError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)?;
+
+        // Do not write any position if None
+        if !self.1.is_none() {
+            write!(f, " ({})", self.1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ParseError {
+    /// Get the [type][ParseErrorType] of this parse error.
+    #[inline(always)]
+    #[must_use]
+    pub const fn err_type(&self) -> &ParseErrorType {
+        &self.0
+    }
+
+    /// Get the [position][Position] of this parse error.
+    #[inline(always)]
+    #[must_use]
+    pub const fn position(&self) -> Position {
+        self.1
+    }
+}
+
+impl From<ParseError> for EvalAltResult {
+    #[inline(always)]
+    fn from(err: ParseError) -> Self {
+        EvalAltResult::ErrorParsing(*err.0, err.1)
+    }
+}
+
+impl From<ParseError> for Box<EvalAltResult> {
+    #[inline(always)]
+    fn from(err: ParseError) -> Self {
+        Box::new(err.into())
+    }
+}
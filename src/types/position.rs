@@ -0,0 +1,124 @@
+use std::fmt;
+
+/// A location (line number + character position) in the input script.
+///
+/// Line and character positions are 1-based; a value of zero means no position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Position {
+    /// Line number: 0 = none
+    line: u32,
+    /// Character position: 0 = BOL
+    pos: u32,
+}
+
+impl Position {
+    /// A [`Position`] representing no position.
+    pub const NONE: Self = Self { line: 0, pos: 0 };
+    /// A [`Position`] representing the first position.
+    pub const START: Self = Self { line: 1, pos: 1 };
+
+    /// Create a new [`Position`].
+    ///
+    /// `line` must not be zero.
+    #[inline]
+    #[must_use]
+    pub const fn new(line: u32, position: u32) -> Self {
+        assert!(line != 0, "line cannot be zero");
+        Self { line, pos: position }
+    }
+
+    /// Get the line number (1-based), or [`None`] if there is no position.
+    #[inline]
+    #[must_use]
+    pub const fn line(self) -> Option<usize> {
+        if self.is_none() {
+            None
+        } else {
+            Some(self.line as usize)
+        }
+    }
+
+    /// Get the character position (1-based), or [`None`] if at beginning of a line.
+    #[inline]
+    #[must_use]
+    pub const fn position(self) -> Option<usize> {
+        if self.is_none() || self.pos == 0 {
+            None
+        } else {
+            Some(self.pos as usize)
+        }
+    }
+
+    /// Is there no [`Position`]?
+    #[inline]
+    #[must_use]
+    pub const fn is_none(self) -> bool {
+        self.line == 0
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_none() {
+            write!(f, "none")
+        } else {
+            write!(f, "line {}, position {}", self.line, self.pos)
+        }
+    }
+}
+
+/// A span of two [`Position`]'s, from the start of a syntax element up to (but excluding) its end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    /// Starting [position][Position].
+    start: Position,
+    /// Ending [position][Position].
+    end: Position,
+}
+
+impl Span {
+    /// An empty [`Span`].
+    pub const NONE: Self = Self::new(Position::NONE, Position::NONE);
+
+    /// Create a new [`Span`].
+    #[inline(always)]
+    #[must_use]
+    pub const fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+
+    /// Is this [`Span`] non-existent?
+    #[inline]
+    #[must_use]
+    pub const fn is_none(&self) -> bool {
+        self.start.is_none() && self.end.is_none()
+    }
+
+    /// Get the [`Span`]'s starting [position][Position].
+    #[inline(always)]
+    #[must_use]
+    pub const fn start(&self) -> Position {
+        self.start
+    }
+
+    /// Get the [`Span`]'s ending [position][Position].
+    #[inline(always)]
+    #[must_use]
+    pub const fn end(&self) -> Position {
+        self.end
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.start.line(), self.end.line()) {
+            (None, None) => write!(f, "none"),
+            (Some(line), Some(end_line)) if line == end_line => write!(
+                f,
+                "line {}, position {}-{}",
+                line, self.start.pos, self.end.pos
+            ),
+            _ => write!(f, "{} to {}", self.start, self.end),
+        }
+    }
+}
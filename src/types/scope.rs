@@ -1,72 +1,378 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::rc::Rc;
 use crate::ast::expression::Expression;
 use crate::ast::function::Function;
 use crate::object::object::Object;
 use crate::object::ta::TA;
-use crate::package::lib::Library;
+use crate::package::lib::{Library, NativeCallContext};
 use crate::package::math::Math;
+use crate::package::string::Str;
+use crate::series::state::BarState;
+use crate::types::native_fn::NativeFunction;
+use crate::types::{Dynamic, FromDynamic, Position};
+use crate::engine::MAX_CALL_STACK_DEPTH;
+use crate::{RhaiResultOf, ERR};
+
+/// A place in a script as reached at run time: the positions of the calls leading to it,
+/// outermost first, followed by its own position.
+pub(crate) type CallSite = Vec<Position>;
+
+/// Nested function calls, shared by a script and the functions it calls.
+#[derive(Debug)]
+pub(crate) struct CallStack {
+    /// Number of function calls currently running.
+    level: Cell<usize>,
+    /// Maximum number of nested calls allowed.
+    max: Cell<usize>,
+    /// Positions of the function calls currently running, outermost first.
+    path: RefCell<Vec<Position>>,
+}
+
+impl Default for CallStack {
+    fn default() -> Self {
+        CallStack {
+            level: Cell::new(0),
+            max: Cell::new(MAX_CALL_STACK_DEPTH),
+            path: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl CallStack {
+    /// Enter a function call, failing once the maximum depth is reached.
+    ///
+    /// The call is left when the returned guard is dropped.
+    pub(crate) fn enter(&self) -> RhaiResultOf<CallGuard<'_>> {
+        let level = self.level.get();
+        if level >= self.max.get() {
+            return Err(ERR::ErrorStackOverflow(Position::NONE).into());
+        }
+        self.level.set(level + 1);
+        Ok(CallGuard(self))
+    }
+
+    /// Record a call made at `pos` until the returned guard is dropped.
+    pub(crate) fn at(&self, pos: Position) -> PathGuard<'_> {
+        self.path.borrow_mut().push(pos);
+        PathGuard(self)
+    }
+
+    /// The place at `pos` within the calls currently running.
+    pub(crate) fn site(&self, pos: Position) -> CallSite {
+        let mut site = self.path.borrow().clone();
+        site.push(pos);
+        site
+    }
+
+    /// Set the maximum number of nested calls allowed.
+    pub(crate) fn set_max(&self, levels: usize) {
+        self.max.set(levels);
+    }
+}
+
+/// A running function call, see [`CallStack::enter`].
+pub(crate) struct CallGuard<'a>(&'a CallStack);
+
+impl Drop for CallGuard<'_> {
+    fn drop(&mut self) {
+        self.0.level.set(self.0.level.get() - 1);
+    }
+}
+
+/// A call recorded in the call path, see [`CallStack::at`].
+pub(crate) struct PathGuard<'a>(&'a CallStack);
+
+impl Drop for PathGuard<'_> {
+    fn drop(&mut self) {
+        self.0.path.borrow_mut().pop();
+    }
+}
+
+/// Variables and functions visible to a script.
+///
+/// A function call runs in a child scope chained to the scope the function was defined in, so
+/// names not found in the child are looked up lexically in the enclosing scopes. The variables
+/// and functions of a scope are shared with the closures defined in it, which therefore see
+/// later changes to them.
 #[derive(Debug)]
 pub struct Scope {
-    pub(crate) variables: RefCell<HashMap<String, Expression>>,
-    functions: RefCell<HashMap<String, Function>>,
+    pub(crate) variables: Rc<RefCell<HashMap<String, Dynamic>>>,
+    /// Names of the variables of this scope that scripts cannot change.
+    constants: Rc<RefCell<HashSet<String>>>,
+    functions: Rc<RefCell<HashMap<String, Function>>>,
     objects: RefCell<HashMap<String, Object>>,
     imports: RefCell<HashMap<String, HashMap<String, Expression>>>, // Module imports
-    libraries:  RefCell<HashMap<String, Box<dyn Library>>>,
+    pub(crate) libraries: Rc<RefCell<HashMap<String, Box<dyn Library>>>>,
+    /// Rust functions registered with the engine running the script.
+    pub(crate) natives: Rc<RefCell<BTreeMap<String, Vec<NativeFunction>>>>,
+    pub(crate) bars: Rc<BarState>,
+    pub(crate) calls: Rc<CallStack>,
+    /// Enclosing scope, searched for names not found in this one.
+    parent: Option<Rc<Scope>>,
 }
 
 impl PartialEq for Scope {
     fn eq(&self, other: &Self) -> bool {
         *self.variables.borrow() == *other.variables.borrow() &&
+            *self.constants.borrow() == *other.constants.borrow() &&
             *self.functions.borrow() == *other.functions.borrow() &&
             *self.objects.borrow() == *other.objects.borrow() &&
             *self.imports.borrow() == *other.imports.borrow()
-        // Note: `libraries` comparison is omitted because `Box<dyn Library>` does not implement `PartialEq`
+        // Note: `libraries` and `natives` comparisons are omitted because functions do not implement `PartialEq`,
+        // and `bars` and `calls` are shared execution state rather than scope contents
     }
 }
 
 impl Clone for Scope {
+    /// Copy the variables and functions of this scope; enclosing scopes stay shared.
     fn clone(&self) -> Self {
         Scope {
-            variables: RefCell::new(self.variables.borrow().clone()),
-            functions: RefCell::new(self.functions.borrow().clone()),
+            variables: Rc::new(RefCell::new(self.variables.borrow().clone())),
+            constants: Rc::new(RefCell::new(self.constants.borrow().clone())),
+            functions: Rc::new(RefCell::new(self.functions.borrow().clone())),
             objects: RefCell::new(self.objects.borrow().clone()),
             imports: RefCell::new(self.imports.borrow().clone()),
-            libraries: RefCell::new(HashMap::new()), // Creating an empty HashMap for libraries
+            libraries: self.libraries.clone(),
+            natives: self.natives.clone(),
+            bars: self.bars.clone(),
+            calls: self.calls.clone(),
+            parent: self.parent.clone(),
         }
     }
 }
 
+impl Default for Scope {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Scope {
     pub fn new() -> Self {
         Scope {
-            variables: RefCell::new(HashMap::new()),
-            functions: RefCell::new(HashMap::new()),
+            variables: Rc::new(RefCell::new(HashMap::new())),
+            constants: Rc::new(RefCell::new(HashSet::new())),
+            functions: Rc::new(RefCell::new(HashMap::new())),
             objects: RefCell::new(HashMap::new()),
             imports: RefCell::new(HashMap::new()),
-            libraries:  RefCell::new(HashMap::new()),
+            libraries: Rc::new(RefCell::new(HashMap::new())),
+            natives: Rc::new(RefCell::new(BTreeMap::new())),
+            bars: Rc::new(BarState::default()),
+            calls: Rc::new(CallStack::default()),
+            parent: None,
+        }
+    }
+
+    /// Create an empty scope chained to `parent`, sharing its libraries, native functions and
+    /// execution state.
+    pub(crate) fn child(parent: &Scope) -> Self {
+        Scope {
+            libraries: parent.libraries.clone(),
+            natives: parent.natives.clone(),
+            bars: parent.bars.clone(),
+            calls: parent.calls.clone(),
+            parent: Some(Rc::new(parent.share())),
+            ..Scope::new()
+        }
+    }
+
+    /// Another handle on this scope, sharing its variables and functions, as captured by closures.
+    pub(crate) fn share(&self) -> Self {
+        Scope {
+            variables: self.variables.clone(),
+            constants: self.constants.clone(),
+            functions: self.functions.clone(),
+            objects: RefCell::new(self.objects.borrow().clone()),
+            imports: RefCell::new(self.imports.borrow().clone()),
+            libraries: self.libraries.clone(),
+            natives: self.natives.clone(),
+            bars: self.bars.clone(),
+            calls: self.calls.clone(),
+            parent: self.parent.clone(),
+        }
+    }
+
+    /// Index of the bar being executed.
+    #[inline]
+    pub fn bar_index(&self) -> usize {
+        self.bars.index.get()
+    }
+
+    /// Commit the variables of the current bar to their history and move on to the next bar.
+    ///
+    /// Variables are cleared, as the script declares them afresh on every bar.
+    pub(crate) fn commit_bar(&self) {
+        let bar = self.bars.index.get();
+        let mut history = self.bars.history.borrow_mut();
+        for (name, value) in self.variables.borrow_mut().drain() {
+            history.entry(name).or_default().set(bar, value);
+        }
+        for series in history.values_mut().filter(|series| series.len() <= bar) {
+            series.set(bar, Dynamic::NA);
         }
+        self.bars.index.set(bar + 1);
     }
 
-    pub fn set_variable(&self, name: String, value: Expression) {
+    /// Add the variable `name` to this scope, replacing any variable of this scope so named.
+    ///
+    /// The value is stored as by [`Dynamic::from`].
+    #[inline]
+    pub fn push<T: Any + Clone>(&mut self, name: impl Into<String>, value: T) -> &mut Self {
+        let name = name.into();
+        self.constants.borrow_mut().remove(&name);
+        self.set_variable(name, Dynamic::from(value));
+        self
+    }
+
+    /// Add the constant `name` to this scope: scripts can read it but neither reassign nor
+    /// redeclare it.
+    #[inline]
+    pub fn push_constant<T: Any + Clone>(&mut self, name: impl Into<String>, value: T) -> &mut Self {
+        let name = name.into();
+        self.constants.borrow_mut().insert(name.clone());
+        self.set_variable(name, Dynamic::from(value));
+        self
+    }
+
+    /// Get the value of the variable `name`, converted to `T`.
+    ///
+    /// Returns [`None`] if there is no such variable or it does not hold a `T`.
+    #[must_use]
+    pub fn get_value<T: FromDynamic>(&self, name: &str) -> Option<T> {
+        self.get_variable(name).and_then(T::from_dynamic)
+    }
+
+    /// Set the value of the variable `name` in the nearest scope that declares it, or else add
+    /// it to this scope.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the variable is a constant.
+    pub fn set_value<T: Any + Clone>(&mut self, name: impl Into<String>, value: T) -> &mut Self {
+        let name = name.into();
+        assert!(!self.is_constant(&name), "cannot modify constant `{}`", name);
+        let value = Dynamic::from(value);
+        if !self.assign_variable(&name, value.clone()) {
+            self.set_variable(name, value);
+        }
+        self
+    }
+
+    /// Remove the variable `name` from this scope, returning its value converted to `T`.
+    ///
+    /// Returns [`None`] if there is no such variable or it does not hold a `T`; the variable is
+    /// removed either way.
+    pub fn remove<T: FromDynamic>(&mut self, name: &str) -> Option<T> {
+        self.constants.borrow_mut().remove(name);
+        self.variables.borrow_mut().remove(name).and_then(T::from_dynamic)
+    }
+
+    /// Is the variable `name`, as found in this scope or the nearest enclosing scope declaring
+    /// it, a constant?
+    #[must_use]
+    pub fn is_constant(&self, name: &str) -> bool {
+        if self.variables.borrow().contains_key(name) {
+            return self.constants.borrow().contains(name);
+        }
+        self.parent.as_ref().map_or(false, |parent| parent.is_constant(name))
+    }
+
+    /// Number of variables in this scope, leaving out enclosing scopes.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.variables.borrow().len()
+    }
+
+    /// Does this scope have no variables of its own?
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.variables.borrow().is_empty()
+    }
+
+    /// Remove all the variables of this scope.
+    #[inline]
+    pub fn clear(&mut self) -> &mut Self {
+        self.variables.borrow_mut().clear();
+        self.constants.borrow_mut().clear();
+        self
+    }
+
+    /// Iterate over the variables of this scope in order of name, as `(name, is_constant, value)`.
+    pub fn iter(&self) -> impl Iterator<Item = (String, bool, Dynamic)> {
+        let mut entries: Vec<_> = self.iter_raw().collect();
+        entries.sort_by(|(a, ..), (b, ..)| a.cmp(b));
+        entries.into_iter()
+    }
+
+    /// Iterate over the variables of this scope in no particular order, as
+    /// `(name, is_constant, value)`.
+    pub fn iter_raw(&self) -> impl Iterator<Item = (String, bool, Dynamic)> {
+        let constants = self.constants.borrow();
+        let entries: Vec<_> = self.variables.borrow().iter()
+            .map(|(name, value)| (name.clone(), constants.contains(name), value.clone()))
+            .collect();
+        entries.into_iter()
+    }
+
+    /// Declare the variable `name` in this scope, shadowing any in enclosing scopes.
+    pub fn set_variable(&self, name: String, value: Dynamic) {
         self.variables.borrow_mut().insert(name, value);
     }
 
-    pub fn get_variable(&self, name: &str) -> Option<Expression> {
-        self.variables.borrow().get(name).cloned()
+    /// Change the value of the variable `name` in the nearest scope that declares it.
+    ///
+    /// Returns `false` if the variable is not declared in this scope or any enclosing scope.
+    pub fn assign_variable(&self, name: &str, value: Dynamic) -> bool {
+        if let Some(variable) = self.variables.borrow_mut().get_mut(name) {
+            *variable = value;
+            return true;
+        }
+        match &self.parent {
+            Some(parent) => parent.assign_variable(name, value),
+            None => false,
+        }
+    }
+
+    /// Get the value of the variable `name` in this scope or the nearest enclosing scope.
+    pub fn get_variable(&self, name: &str) -> Option<Dynamic> {
+        match self.variables.borrow().get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.parent.as_ref()?.get_variable(name),
+        }
+    }
+
+    /// Does `name` refer to a variable of the outermost scope, rather than one declared in a
+    /// function or block scope in between?
+    pub(crate) fn is_top_level(&self, name: &str) -> bool {
+        match &self.parent {
+            Some(parent) => !self.variables.borrow().contains_key(name) && parent.is_top_level(name),
+            None => true,
+        }
     }
 
     pub fn set_function(&self, name: String, function: Function) {
         self.functions.borrow_mut().insert(name, function);
     }
 
+    /// Get the function `name` defined in this scope or the nearest enclosing scope.
     pub fn get_function(&self, name: &str) -> Option<Function> {
-        self.functions.borrow().get(name).cloned()
+        self.resolve_function(name).map(|(function, _)| function)
+    }
+
+    /// Get the function `name` together with the scope it is defined in.
+    pub(crate) fn resolve_function(&self, name: &str) -> Option<(Function, Scope)> {
+        match self.functions.borrow().get(name) {
+            Some(function) => Some((function.clone(), self.share())),
+            None => self.parent.as_ref()?.resolve_function(name),
+        }
     }
 
     pub fn set_object(&self, name: String, object: Object) {
-        println!("{:?},{:?}", name, object);
         self.objects.borrow_mut().insert(name, object);
     }
 
@@ -96,32 +402,86 @@ impl Scope {
 
     }
 
-    pub fn call_library_function(&self, lib_name: &str, func_name: &str, args: Vec<Expression>) -> Result<Expression, String> {
-        println!("call_library_function: lib_name: {:?}, func_name: {:?}, args: {:?}", lib_name, func_name, args);
-        println!("libraries: {:?}", self.libraries);
-
-        // Get the library
-        if let Some(lib) = self.libraries.borrow().get(lib_name) {
-
-            let cc=lib.call_method(func_name, args);
-            println!("----{:?}",cc);
-            cc
-
-            // Call the method and handle the Result
-            // match lib.call_method(func_name, args) {
-            //     Ok(result) => Ok(result), // Return the result
-            //     Err(err) => Err(err),     // Return the error
-            // }
-        } else {
-            Err(format!("Library '{}' not found", lib_name)) // Return error if library is not found
+    pub fn call_library_function(&self, lib_name: &str, func_name: &str, args: Vec<Dynamic>, pos: Position) -> RhaiResultOf<Dynamic> {
+        match self.libraries.borrow().get(lib_name) {
+            Some(lib) => lib.call_method(&NativeCallContext::new(func_name, pos, self), func_name, args),
+            None => Err(ERR::ErrorFunctionNotFound(format!("{}.{}", lib_name, func_name), Position::NONE).into()),
         }
     }
 
 
-    pub fn import_library(&mut self, library_name: &str) {
+    /// Make the built-in library `library_name` available in this scope.
+    pub fn import_library(&mut self, library_name: &str) -> RhaiResultOf<()> {
         match library_name {
-            "math" => self.register_library("math", Box::new(Math)),
-            _ => eprintln!("Library '{}' is not recognized", library_name),
+            "math" => self.register_library("math", Box::new(Math::new())),
+            "ta" => self.register_library("ta", Box::new(TA::new())),
+            "str" => self.register_library("str", Box::new(Str::new())),
+            _ => return Err(format!("library `{}` is not recognized", library_name).into()),
         }
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine, INT};
+
+    #[test]
+    fn test_scope_values() {
+        let mut scope = Scope::new();
+        scope.push("x", 42 as INT).push_constant("LIMIT", 10 as INT).push("name", "bob".to_string());
+        assert_eq!(scope.len(), 3);
+        assert_eq!(scope.get_value::<INT>("x"), Some(42));
+        assert_eq!(scope.get_value::<String>("x"), None);
+
+        scope.set_value("x", 1 as INT).set_value("y", true);
+        assert_eq!(scope.get_value::<INT>("x"), Some(1));
+        assert_eq!(scope.get_value::<bool>("y"), Some(true));
+
+        let entries: Vec<_> = scope.iter().map(|(name, constant, _)| (name, constant)).collect();
+        assert_eq!(entries, vec![
+            ("LIMIT".to_string(), true),
+            ("name".to_string(), false),
+            ("x".to_string(), false),
+            ("y".to_string(), false),
+        ]);
+        assert_eq!(scope.iter_raw().count(), 4);
+
+        assert_eq!(scope.remove::<String>("name"), Some("bob".to_string()));
+        assert_eq!(scope.len(), 3);
+        scope.clear();
+        assert!(scope.is_empty());
+        assert!(!scope.is_constant("LIMIT"));
+        scope.import_library("math").unwrap();
+        let err = scope.import_library("net").unwrap_err();
+        assert_eq!(err.to_string(), "library `net` is not recognized");
+    }
+
+    #[test]
+    fn test_scope_constants() {
+        let mut engine = Engine::new();
+        let mut scope = Scope::new();
+        scope.push_constant("LIMIT", 10 as INT).push("n", 1 as INT);
+        engine.run_scope("n += LIMIT\nif true\n    LIMIT = 3\n", &mut scope).unwrap();
+        assert_eq!(scope.get_value::<INT>("n"), Some(11));
+
+        let err = engine.run_scope("LIMIT := 3\n", &mut scope).unwrap_err();
+        assert!(matches!(*err, ERR::ErrorAssignmentToConstant(..)));
+        assert_eq!(err.to_string(), "Cannot modify constant: LIMIT (line 1, position 1)");
+        let err = engine.run_scope("n = 0\nLIMIT = 3\n", &mut scope).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot modify constant: LIMIT (line 2, position 1)");
+        assert_eq!(scope.get_value::<INT>("LIMIT"), Some(10));
+
+        // Calling a function with a constant argument leaves the constant alone
+        engine.run_scope("f(x) => [5]\nf(LIMIT)\n", &mut scope).unwrap();
+        assert_eq!(scope.get_value::<INT>("LIMIT"), Some(10));
+        let err = engine.run_scope("f(x) => [5]\n[LIMIT] = f(LIMIT)\n", &mut scope).unwrap_err();
+        assert!(matches!(*err, ERR::ErrorAssignmentToConstant(..)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    #[should_panic(expected = "cannot modify constant `LIMIT`")]
+    fn test_scope_set_constant() {
+        Scope::new().push_constant("LIMIT", 10 as INT).set_value("LIMIT", 3 as INT);
+    }
+}
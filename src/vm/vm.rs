@@ -1,43 +1,18 @@
-use std::cell::RefCell;
 use crate::{Engine, RhaiResultOf, Scope};
-use crate::ast::atom::Atom;
-use crate::ast::expression::Expression;
-use crate::package::math::Math;
 
 impl Engine {
-
+    /// Run a script, discarding its result.
     pub fn run(&mut self, code: &str) -> RhaiResultOf<()> {
-
-        let mut context = Scope::new();
-        //context.set_variable("ta".to_string(), Expression::Atom(Atom::Variable("ta".to_string())));
-        context.register_library("math", Box::new(Math));
-
-        let result = self.run_ast(code);
-
-
-        match result {
-            Ok(vars) => {
-                println!("Variables: {:?}", vars);
-
-            }
-            Err(e) => println!("Error: {}", e),
-        }
-        Ok(())
+        let ast = self.compile(code)?;
+        self.run_ast_with_scope(&mut Scope::new(), &ast)
     }
 
-    pub fn run_scope(&mut self, code: &str,scope:&mut Scope) -> RhaiResultOf<()> {
-
-
-        let result = self.run_ast(code);
-
-
-        match result {
-            Ok(vars) => {
-                println!("Variables: {:?}", vars);
-
-            }
-            Err(e) => println!("Error: {}", e),
-        }
-        Ok(())
+    /// Run a script with `scope`, discarding its result.
+    ///
+    /// The script sees the variables of `scope`, and the variables it declares at the top level
+    /// are left in it.
+    pub fn run_scope(&mut self, code: &str, scope: &mut Scope) -> RhaiResultOf<()> {
+        let ast = self.compile(code)?;
+        self.run_ast_with_scope(scope, &ast)
     }
-}
\ No newline at end of file
+}